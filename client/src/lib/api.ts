@@ -0,0 +1,20 @@
+export const API_URL = 'http://localhost:3000';
+
+/**
+ * Fetches every item of a paginated list endpoint, following each page's
+ * `next` link until the last page.
+ */
+export async function fetchAllPages<T = any>(path: string, init?: RequestInit): Promise<T[]> {
+    const items: T[] = [];
+    let next: string | null = path;
+    while (next) {
+        const res = await fetch(`${API_URL}${next}`, init);
+        if (!res.ok) {
+            throw new Error(await res.text());
+        }
+        const page = await res.json();
+        items.push(...page.items);
+        next = page.next ?? null;
+    }
+    return items;
+}
@@ -9,7 +9,10 @@ const initialState: StyleIntent = {
     bibliography_preset: null,
     detailed_config: null,
     author_format: null,
-    has_bibliography: null
+    has_bibliography: null,
+    bibliography_sort: null,
+    bibliography_grouping: null,
+    subsequent_author_substitute: null
 };
 
 export const intent = writable<StyleIntent>(initialState);
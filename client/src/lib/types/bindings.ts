@@ -6,7 +6,7 @@
  * This struct captures the state of the "Decision Wizard" and is used
  * to generate the next set of questions or the final CSL style.
  */
-export type StyleIntent = { base_archetype: string | null; field: string | null; class: CitationClass | null; author_format: NameOptions | null; has_bibliography: boolean | null; citation_preset: string | null; bibliography_preset: string | null; detailed_config: boolean | null };
+export type StyleIntent = { base_archetype: string | null; field: string | null; class: CitationClass | null; author_format: NameOptions | null; has_bibliography: boolean | null; citation_preset: string | null; bibliography_preset: string | null; detailed_config: boolean | null; bibliography_sort: BibliographySort | null; bibliography_grouping: BibliographyGrouping | null; subsequent_author_substitute: string | null };
 
 export type CitationClass = "author_date" | "footnote" | "endnote" | "numeric";
 
@@ -16,12 +16,55 @@ export type NameForm = "long" | "short";
 
 export type EtAlConfig = { min: number; use_first: number };
 
+/**
+ * Primary sort key for bibliography entries. Ties are broken by the
+ * remaining keys in author, date, title order.
+ */
+export type BibliographySort = "author" | "date" | "title" | "citation_order";
+
+/**
+ * How bibliography entries are grouped under headings.
+ */
+export type BibliographyGrouping = "reference_type" | "language";
+
 /**
  * A package returned by the backend containing everything the frontend
  * needs to render the next step in the wizard.
  */
-export type DecisionPackage = { missing_fields: string[]; question: Question | null; previews: Preview[]; in_text_preview: string | null; note_preview: string | null; bibliography_preview: string | null };
+export type DecisionPackage = { missing_fields: string[]; question: Question | null; previews: Preview[]; in_text_preview: string | null; note_preview: NotePreview[] | null; bibliography_preview: string | null; fragments: PreviewFragment[] };
+
+/**
+ * A CSL 1.0 style generated from CSLN.
+ */
+export type CslExport = { xml: string; lost_features: string[] };
+
+/**
+ * A piece of rendered preview output traced back to what produced it,
+ * so a click on it can open the wizard question that controls it.
+ */
+export type PreviewFragment = { context: PreviewContext; text: string; component: string; reference_id: string | null; intent_field: string | null };
+
+/**
+ * Where in a preview a fragment was rendered.
+ */
+export type PreviewContext = "citation" | "note" | "bibliography";
+
+/**
+ * A single rendered footnote or endnote.
+ */
+export type NotePreview = { number: number; reference_id: string; position: NotePosition; text: string };
+
+/**
+ * Where a note falls relative to earlier citations of the same source.
+ */
+export type NotePosition = "first" | "subsequent" | "ibid";
 
 export type Question = { id: string; text: string; description: string | null };
 
-export type Preview = { label: string; html: string; choice_value: any };
+export type Preview = { label: string; html: string; choice_value: StyleIntentPatch };
+
+/**
+ * A partial update to a `StyleIntent`.
+ * Fields left as `None` keep their current value when the patch is applied.
+ */
+export type StyleIntentPatch = { base_archetype?: string | null; field?: string | null; class?: CitationClass | null; author_format?: NameOptions | null; has_bibliography?: boolean | null; citation_preset?: string | null; bibliography_preset?: string | null; detailed_config?: boolean | null; bibliography_sort?: BibliographySort | null; bibliography_grouping?: BibliographyGrouping | null; subsequent_author_substitute?: string | null };
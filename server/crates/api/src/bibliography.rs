@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use csln_core::Style;
+use csln_processor::{Processor, Reference, Bibliography};
+use intent_engine::{BibliographyGrouping, BibliographySort, StyleIntent};
+
+use crate::corpus::ReferenceMeta;
+use crate::html;
+
+/// Ordering and grouping applied to rendered bibliography entries.
+/// Subsequent-author substitution is left to the processor, which reads it
+/// from the style.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BibliographyOptions {
+    pub sort: Option<BibliographySort>,
+    pub grouping: Option<BibliographyGrouping>,
+}
+
+impl BibliographyOptions {
+    pub fn from_intent(intent: &StyleIntent) -> Self {
+        BibliographyOptions {
+            sort: Some(intent.effective_bibliography_sort()),
+            grouping: intent.bibliography_grouping,
+        }
+    }
+}
+
+/// A run of bibliography entries under an optional heading.
+#[derive(Debug, Clone, Serialize)]
+pub struct BibliographyGroup {
+    pub heading: Option<String>,
+    pub entries: Vec<BibliographyEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BibliographyEntry {
+    pub reference_id: String,
+    pub text: String,
+    /// The template components that rendered `text`, for annotation.
+    #[serde(skip)]
+    pub components: Vec<html::Component>,
+}
+
+/// Renders one bibliography entry per cited reference, returned in
+/// citation order.
+///
+/// All references go through a single processor so citation numbers and
+/// year suffixes (2020a, 2020b) are assigned across the whole set. The
+/// processor's output carries no reference ids, so entries are matched back
+/// to references by their title, first author and year.
+pub fn render_entries(
+    style: &Style,
+    cite_ids: &[String],
+    references: &HashMap<String, Reference>,
+    metadata: &HashMap<String, ReferenceMeta>,
+) -> Vec<BibliographyEntry> {
+    let bib: Bibliography = cite_ids.iter()
+        .filter_map(|id| references.get(id).map(|r| (id.clone(), r.clone())))
+        .collect();
+    let ids: Vec<String> = bib.keys().cloned().collect();
+    let output = Processor::new(style.clone(), bib).process_references();
+    let rendered = output.bibliography.iter()
+        .map(|entry| (
+            csln_processor::citation_to_string(entry, None, None, None, None),
+            html::Component::from_entry(entry),
+        ))
+        .collect();
+    match_entries(rendered, &ids, metadata)
+}
+
+/// Pairs rendered entries with the references they came from, returning
+/// them in the order of `ids`.
+///
+/// Each entry goes to the unclaimed reference whose title, first author
+/// and year it contains, weighted in that order; entries that match
+/// nothing take the remaining references in turn.
+fn match_entries(
+    rendered: Vec<(String, Vec<html::Component>)>,
+    ids: &[String],
+    metadata: &HashMap<String, ReferenceMeta>,
+) -> Vec<BibliographyEntry> {
+    let mut claimed: Vec<Option<(String, Vec<html::Component>)>> = vec![None; ids.len()];
+    let mut unmatched = Vec::new();
+
+    for entry in rendered {
+        let lower = entry.0.to_lowercase();
+        let best = ids.iter()
+            .enumerate()
+            .filter(|(i, _)| claimed[*i].is_none())
+            .map(|(i, id)| (i, metadata.get(id).map(|meta| match_score(&lower, meta)).unwrap_or(0)))
+            .filter(|(_, score)| *score > 0)
+            // Earliest cited wins ties
+            .max_by_key(|(i, score)| (*score, std::cmp::Reverse(*i)));
+        match best {
+            Some((i, _)) => claimed[i] = Some(entry),
+            None => unmatched.push(entry),
+        }
+    }
+
+    let mut unmatched = unmatched.into_iter();
+    ids.iter()
+        .zip(claimed)
+        .filter_map(|(id, entry)| {
+            let (text, components) = entry.or_else(|| unmatched.next())?;
+            Some(BibliographyEntry { reference_id: id.clone(), text, components })
+        })
+        .collect()
+}
+
+fn match_score(lower: &str, meta: &ReferenceMeta) -> u32 {
+    let contains = |value: &str| !value.is_empty() && lower.contains(&value.to_lowercase());
+    let mut score = 0;
+    if meta.title.as_deref().is_some_and(contains) {
+        score += 4;
+    }
+    if meta.authors.first().is_some_and(|author| contains(author)) {
+        score += 2;
+    }
+    if meta.year.as_deref().is_some_and(contains) {
+        score += 1;
+    }
+    score
+}
+
+/// Sorts and groups rendered entries. `entries` must be in citation order.
+pub fn arrange(
+    mut entries: Vec<BibliographyEntry>,
+    metadata: &HashMap<String, ReferenceMeta>,
+    options: &BibliographyOptions,
+) -> Vec<BibliographyGroup> {
+    let meta_for = |id: &str| metadata.get(id).cloned().unwrap_or_default();
+
+    match options.sort.unwrap_or(BibliographySort::Author) {
+        BibliographySort::CitationOrder => {},
+        sort => entries.sort_by_cached_key(|entry| sort_key(&meta_for(&entry.reference_id), sort)),
+    }
+
+    // Group in order of first appearance, keeping the sort within each group
+    let mut grouped: Vec<BibliographyGroup> = Vec::new();
+    for entry in entries {
+        let heading = options.grouping.map(|grouping| group_heading(&meta_for(&entry.reference_id), grouping));
+        match grouped.iter_mut().find(|group| group.heading == heading) {
+            Some(group) => group.entries.push(entry),
+            None => grouped.push(BibliographyGroup { heading, entries: vec![entry] }),
+        }
+    }
+    grouped
+}
+
+fn sort_key(meta: &ReferenceMeta, sort: BibliographySort) -> Vec<String> {
+    let author = meta.authors.join(" ").to_lowercase();
+    let date = meta.year.clone().unwrap_or_default();
+    let title = meta.title.clone().unwrap_or_default().to_lowercase();
+    match sort {
+        BibliographySort::Date => vec![date, author, title],
+        BibliographySort::Title => vec![title, author, date],
+        BibliographySort::Author | BibliographySort::CitationOrder => vec![author, date, title],
+    }
+}
+
+fn group_heading(meta: &ReferenceMeta, grouping: BibliographyGrouping) -> String {
+    match grouping {
+        BibliographyGrouping::ReferenceType => heading_case(&meta.ref_type),
+        BibliographyGrouping::Language => match &meta.language {
+            Some(language) => language_name(language),
+            None => "Unspecified".to_string(),
+        },
+    }
+}
+
+/// "de-AT" -> "German"; unlisted languages keep their tag.
+fn language_name(tag: &str) -> String {
+    let primary = tag.split(['-', '_']).next().unwrap_or(tag).to_lowercase();
+    let name = match primary.as_str() {
+        "de" => "German",
+        "en" => "English",
+        "es" => "Spanish",
+        "fr" => "French",
+        "it" => "Italian",
+        "la" => "Latin",
+        "nl" => "Dutch",
+        "pt" => "Portuguese",
+        "ru" => "Russian",
+        "zh" => "Chinese",
+        "ja" => "Japanese",
+        _ => return tag.to_string(),
+    };
+    name.to_string()
+}
+
+/// "edited-book" -> "Edited Book"
+fn heading_case(value: &str) -> String {
+    value.split(['-', '_'])
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Renders groups as an HTML list, with a heading before each named group.
+pub fn to_html(
+    groups: &[BibliographyGroup],
+    metadata: &HashMap<String, ReferenceMeta>,
+    trace: &mut html::Trace,
+) -> String {
+    let mut html = String::from("<div class=\"csl-bib-body\" role=\"list\">");
+    for group in groups {
+        if let Some(heading) = &group.heading {
+            html.push_str(&html::group_heading(heading, trace));
+        }
+        for entry in &group.entries {
+            let meta = metadata.get(&entry.reference_id).cloned().unwrap_or_else(|| ReferenceMeta {
+                id: entry.reference_id.clone(),
+                ..Default::default()
+            });
+            html.push_str(&html::bibliography_entry(&entry.text, &entry.components, &meta, trace));
+        }
+    }
+    html.push_str("</div>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(id: &str, author: &str, year: &str, title: &str, ref_type: &str, language: Option<&str>) -> ReferenceMeta {
+        ReferenceMeta {
+            id: id.to_string(),
+            ref_type: ref_type.to_string(),
+            title: Some(title.to_string()),
+            authors: vec![author.to_string()],
+            year: Some(year.to_string()),
+            language: language.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn corpus() -> HashMap<String, ReferenceMeta> {
+        [
+            meta("smith_b", "Smith", "2020", "Second Thoughts", "book", Some("en")),
+            meta("einstein", "Einstein", "1905", "Zur Elektrodynamik bewegter Körper", "article", Some("de")),
+            meta("smith_a", "Smith", "2020", "A First Look", "article", Some("en-GB")),
+        ]
+        .into_iter()
+        .map(|m| (m.id.clone(), m))
+        .collect()
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn entries(pairs: &[(&str, &str)]) -> Vec<BibliographyEntry> {
+        pairs.iter()
+            .map(|(id, text)| BibliographyEntry {
+                reference_id: id.to_string(),
+                text: text.to_string(),
+                components: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn rendered(texts: &[&str]) -> Vec<(String, Vec<html::Component>)> {
+        texts.iter().map(|text| (text.to_string(), Vec::new())).collect()
+    }
+
+    fn pairs(entries: &[BibliographyEntry]) -> Vec<(&str, &str)> {
+        entries.iter().map(|e| (e.reference_id.as_str(), e.text.as_str())).collect()
+    }
+
+    #[test]
+    fn matches_processor_output_back_to_references() {
+        // Sorted and disambiguated by the processor, so the order differs
+        // from citation order and the years carry suffixes
+        let texts = rendered(&[
+            "Einstein, A. (1905). Zur Elektrodynamik bewegter Körper.",
+            "Smith, J. (2020a). A first look.",
+            "Smith, J. (2020b). Second thoughts.",
+        ]);
+        let matched = match_entries(texts, &ids(&["smith_b", "einstein", "smith_a"]), &corpus());
+        assert_eq!(pairs(&matched), [
+            ("smith_b", "Smith, J. (2020b). Second thoughts."),
+            ("einstein", "Einstein, A. (1905). Zur Elektrodynamik bewegter Körper."),
+            ("smith_a", "Smith, J. (2020a). A first look."),
+        ]);
+    }
+
+    #[test]
+    fn unmatched_entries_fill_remaining_references_in_order() {
+        let texts = rendered(&["[1] Unrecognisable.", "Einstein 1905"]);
+        let matched = match_entries(texts, &ids(&["smith_a", "einstein"]), &corpus());
+        assert_eq!(pairs(&matched), [
+            ("smith_a", "[1] Unrecognisable."),
+            ("einstein", "Einstein 1905"),
+        ]);
+    }
+
+    #[test]
+    fn sorts_and_groups_by_language() {
+        let options = BibliographyOptions {
+            sort: Some(BibliographySort::Title),
+            grouping: Some(BibliographyGrouping::Language),
+        };
+        let groups = arrange(entries(&[
+            ("smith_b", "Smith 2020b"),
+            ("einstein", "Einstein 1905"),
+            ("smith_a", "Smith 2020a"),
+        ]), &corpus(), &options);
+
+        let headings: Vec<Option<&str>> = groups.iter().map(|g| g.heading.as_deref()).collect();
+        assert_eq!(headings, [Some("English"), Some("German")]);
+        let english: Vec<&str> = groups[0].entries.iter().map(|e| e.reference_id.as_str()).collect();
+        assert_eq!(english, ["smith_a", "smith_b"]);
+    }
+
+    #[test]
+    fn citation_order_keeps_input_order() {
+        let options = BibliographyOptions { sort: Some(BibliographySort::CitationOrder), ..Default::default() };
+        let groups = arrange(entries(&[("smith_b", "b"), ("einstein", "e")]), &corpus(), &options);
+        let order: Vec<&str> = groups[0].entries.iter().map(|e| e.reference_id.as_str()).collect();
+        assert_eq!(order, ["smith_b", "einstein"]);
+    }
+
+    #[test]
+    fn author_substitution_is_left_to_the_processor() {
+        let intent = StyleIntent {
+            subsequent_author_substitute: Some("———".to_string()),
+            ..Default::default()
+        };
+        let options = BibliographyOptions::from_intent(&intent);
+        // As rendered by a processor that already substituted the repeat
+        let groups = arrange(entries(&[
+            ("smith_a", "Smith. 2020a. A first look."),
+            ("smith_b", "———. 2020b. Second thoughts."),
+        ]), &corpus(), &options);
+        let texts: Vec<&str> = groups[0].entries.iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(texts, ["Smith. 2020a. A first look.", "———. 2020b. Second thoughts."]);
+
+        // Repeated authors the processor left alone stay as they are
+        let groups = arrange(entries(&[
+            ("smith_a", "Smith. 2020a. A first look."),
+            ("smith_b", "Smith. 2020b. Second thoughts."),
+        ]), &corpus(), &options);
+        assert_eq!(groups[0].entries[1].text, "Smith. 2020b. Second thoughts.");
+    }
+
+    #[test]
+    fn names_languages_and_keeps_unknown_tags() {
+        assert_eq!(language_name("de-AT"), "German");
+        assert_eq!(language_name("EN"), "English");
+        assert_eq!(language_name("tlh"), "tlh");
+    }
+}
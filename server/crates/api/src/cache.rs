@@ -0,0 +1,177 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use serde::Serialize;
+use csln_core::Style;
+
+/// Default number of rendered previews kept in memory.
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// A bounded, least-recently-used cache of rendered output.
+///
+/// Keys are serializations of everything that affects a render (see
+/// [`render_key`]), so identical wizard states share one entry regardless of
+/// which endpoint asked for them, and different states never do.
+pub struct RenderCache<V> {
+    capacity: usize,
+    inner: Mutex<CacheInner<V>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct CacheInner<V> {
+    entries: HashMap<String, V>,
+    /// Keys from least to most recently used.
+    order: VecDeque<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+impl<V: Clone> RenderCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        RenderCache {
+            capacity: capacity.max(1),
+            inner: Mutex::new(CacheInner {
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.entries.get(key).cloned() {
+            Some(value) => {
+                inner.order.retain(|k| k != key);
+                inner.order.push_back(key.to_string());
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(value)
+            },
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: String, value: V) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.entries.insert(key.clone(), value).is_some() {
+            inner.order.retain(|k| *k != key);
+        }
+        inner.order.push_back(key);
+
+        while inner.entries.len() > self.capacity {
+            match inner.order.pop_front() {
+                Some(oldest) => { inner.entries.remove(&oldest); },
+                None => break,
+            }
+        }
+    }
+
+    /// Returns the cached value for `key`, rendering and storing it on a miss.
+    pub fn get_or_insert_with(&self, key: String, render: impl FnOnce() -> V) -> V {
+        if let Some(value) = self.get(&key) {
+            return value;
+        }
+        // Render outside the lock so slow renders don't block other requests
+        let value = render();
+        self.insert(key, value.clone());
+        value
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.inner.lock().unwrap();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: inner.entries.len(),
+            capacity: self.capacity,
+        }
+    }
+}
+
+/// Serializes a style together with the references and citation context it
+/// is rendered against.
+///
+/// The whole serialization is the key rather than a hash of it, so two
+/// different renders can never collide and serve each other's output.
+pub fn render_key(style: &Style, reference_ids: &[String], context: &str) -> String {
+    serde_json::to_string(&(style, reference_ids, context)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intent_engine::StyleIntent;
+
+    fn key(n: usize) -> String {
+        format!("key-{}", n)
+    }
+
+    #[test]
+    fn evicts_least_recently_used_when_full() {
+        let cache = RenderCache::new(2);
+        cache.insert(key(1), 1);
+        cache.insert(key(2), 2);
+        cache.insert(key(3), 3);
+        assert_eq!(cache.get(&key(1)), None);
+        assert_eq!(cache.get(&key(2)), Some(2));
+        assert_eq!(cache.get(&key(3)), Some(3));
+        assert_eq!(cache.stats().entries, 2);
+    }
+
+    #[test]
+    fn get_refreshes_recency() {
+        let cache = RenderCache::new(2);
+        cache.insert(key(1), 1);
+        cache.insert(key(2), 2);
+        assert_eq!(cache.get(&key(1)), Some(1));
+        cache.insert(key(3), 3);
+        assert_eq!(cache.get(&key(1)), Some(1));
+        assert_eq!(cache.get(&key(2)), None);
+    }
+
+    #[test]
+    fn reinserting_a_key_keeps_one_entry() {
+        let cache = RenderCache::new(2);
+        cache.insert(key(1), 1);
+        cache.insert(key(1), 10);
+        cache.insert(key(2), 2);
+        assert_eq!(cache.get(&key(1)), Some(10));
+        assert_eq!(cache.get(&key(2)), Some(2));
+        assert_eq!(cache.inner.lock().unwrap().order.len(), 2);
+    }
+
+    #[test]
+    fn renders_only_on_a_miss() {
+        let cache = RenderCache::new(4);
+        assert_eq!(cache.get_or_insert_with(key(1), || 1), 1);
+        assert_eq!(cache.get_or_insert_with(key(1), || unreachable!()), 1);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+    }
+
+    #[test]
+    fn keys_distinguish_every_input() {
+        let style = StyleIntent::default().to_style();
+        let ids = vec!["a".to_string(), "b".to_string()];
+        let base = render_key(&style, &ids, "ctx");
+        assert_eq!(base, render_key(&style, &ids, "ctx"));
+        assert_ne!(base, render_key(&style, &ids[..1], "ctx"));
+        assert_ne!(base, render_key(&style, &ids, "other"));
+        // Ids and context are delimited, so shifting text between them changes the key
+        assert_ne!(
+            render_key(&style, &["ab".to_string()], "c"),
+            render_key(&style, &["a".to_string()], "bc"),
+        );
+    }
+}
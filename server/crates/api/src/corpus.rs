@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Number of references cited in a preview when none are pinned.
+pub const PREVIEW_LIMIT: usize = 3;
+
+/// Page cited for the first reference in a preview, so locator formatting
+/// (": 34", ", p. 34") shows up and can be clicked.
+pub const PREVIEW_LOCATOR: &str = "34";
+
+/// Lightweight metadata extracted from a reference in the corpus.
+///
+/// CSLN references are strongly typed, but for previews we only need a few
+/// loosely-shaped fields (type, keywords, title, names, year, language), so
+/// they are read from the raw document rather than the `Reference` enum.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReferenceMeta {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub ref_type: String,
+    pub keywords: Vec<String>,
+    pub title: Option<String>,
+    /// Family names (or literal names) of the primary contributors.
+    pub authors: Vec<String>,
+    pub year: Option<String>,
+    pub language: Option<String>,
+}
+
+impl ReferenceMeta {
+    /// Builds metadata from a serialized reference (CSLN YAML or JSON).
+    pub fn from_value(id: &str, value: &Value) -> Self {
+        let ref_type = value.get("type")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown")
+            .to_string();
+
+        let keywords = value.get("keywords")
+            .and_then(Value::as_array)
+            .map(|kws| kws.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default();
+
+        let title = match value.get("title") {
+            Some(Value::String(s)) => Some(s.clone()),
+            Some(Value::Object(obj)) => obj.get("main").and_then(Value::as_str).map(str::to_string),
+            _ => None,
+        };
+
+        // Fall back to editors for edited works without authors
+        let names = value.get("author").or_else(|| value.get("editor"));
+        let authors = match names {
+            Some(Value::Array(list)) => list.iter().filter_map(contributor_name).collect(),
+            Some(other) => contributor_name(other).into_iter().collect(),
+            None => Vec::new(),
+        };
+
+        let year = value.get("issued")
+            .and_then(Value::as_str)
+            .map(|s| s.chars().take(4).collect::<String>())
+            .filter(|y| y.len() == 4 && y.chars().all(|c| c.is_ascii_digit()));
+
+        let language = value.get("language")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        ReferenceMeta {
+            id: id.to_string(),
+            ref_type,
+            keywords,
+            title,
+            authors,
+            year,
+            language,
+        }
+    }
+}
+
+/// Query filters for narrowing the corpus, e.g. `?keyword=law&type=article`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ReferenceFilter {
+    /// Comma-separated keywords; a reference matches if it has any of them.
+    pub keyword: Option<String>,
+    #[serde(rename = "type")]
+    pub ref_type: Option<String>,
+    /// Academic field as used by `StyleIntent::field` (e.g. "humanities").
+    pub field: Option<String>,
+}
+
+impl ReferenceFilter {
+    pub fn from_keywords(keywords: &[String]) -> Self {
+        ReferenceFilter {
+            keyword: Some(keywords.join(",")),
+            ..Default::default()
+        }
+    }
+
+    pub fn matches(&self, meta: &ReferenceMeta) -> bool {
+        if let Some(keywords) = &self.keyword {
+            let wanted = split_list(keywords);
+            if !wanted.is_empty() && !wanted.iter().any(|k| meta.keywords.contains(k)) {
+                return false;
+            }
+        }
+        if let Some(ref_type) = &self.ref_type {
+            if &meta.ref_type != ref_type {
+                return false;
+            }
+        }
+        if let Some(field) = &self.field {
+            let keyword = field_keyword(field);
+            if !meta.keywords.iter().any(|k| k == keyword) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Ids of all matching references, sorted for stable output.
+    pub fn matching_ids(&self, metadata: &HashMap<String, ReferenceMeta>) -> Vec<String> {
+        let mut ids: Vec<String> = metadata.values()
+            .filter(|meta| self.matches(meta))
+            .map(|meta| meta.id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+}
+
+/// Maps a wizard field value onto the keyword used in the corpus.
+fn field_keyword(field: &str) -> &str {
+    match field {
+        "social_science" => "social-sciences",
+        "sciences" => "hard-sciences",
+        other => other,
+    }
+}
+
+/// Splits a comma-separated query value, dropping empty items.
+pub fn split_list(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn contributor_name(value: &Value) -> Option<String> {
+    match value {
+        Value::Object(obj) => obj.get("family")
+            .or_else(|| obj.get("name"))
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        Value::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Preferred preview references for an academic field, in display order.
+fn field_candidates(field: Option<&str>) -> &'static [&'static str] {
+    match field {
+        Some("humanities") => &["foucault_discipline", "austen_pride", "met_catalog_2020"],
+        Some("social_science") => &["berger_luckmann", "pew_social_media", "who_report"],
+        Some("sciences") => &["vaswani_attention", "aad_atlas_higgs", "einstein_relativity"],
+        // A diverse mix that shows off style features before a field is chosen
+        _ => &["vaswani_attention", "foucault_discipline", "brown_v_board"],
+    }
+}
+
+/// Picks the reference ids to cite in a preview.
+///
+/// Pinned ids win when any of them exist in the corpus; otherwise the
+/// field's candidates are used, falling back to the first ids in sorted
+/// order so the selection is stable across restarts.
+pub fn select_preview_ids<V>(
+    field: Option<&str>,
+    references: &HashMap<String, V>,
+    pinned: Option<&[String]>,
+) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for id in pinned.unwrap_or_default() {
+        if references.contains_key(id) && !ids.contains(id) {
+            ids.push(id.clone());
+        }
+    }
+    if !ids.is_empty() {
+        return ids;
+    }
+
+    ids = field_candidates(field).iter()
+        .filter(|id| references.contains_key(**id))
+        .map(|id| id.to_string())
+        .collect();
+
+    if ids.is_empty() {
+        let mut keys: Vec<&String> = references.keys().collect();
+        keys.sort();
+        ids = keys.into_iter().take(PREVIEW_LIMIT).cloned().collect();
+    }
+    ids
+}
+
+/// Removes markup from rendered output so it can be inspected as plain text.
+pub fn strip_tags(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
@@ -0,0 +1,310 @@
+use axum::{extract::State, http::StatusCode, Json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use csln_core::Style;
+use csln_processor::{Processor, Reference, Bibliography, Citation, CitationItem};
+use intent_engine::StyleIntent;
+
+use crate::corpus::{strip_tags, ReferenceMeta};
+use crate::{html, AppState};
+
+/// Built-in references that collide in short citation forms.
+const SCENARIO_YAML: &str = include_str!("../../../resources/disambiguation.yaml");
+
+/// A set of references that can only be told apart by disambiguation.
+struct Scenario {
+    id: &'static str,
+    description: &'static str,
+    reference_ids: &'static [&'static str],
+}
+
+const SCENARIOS: [Scenario; 3] = [
+    Scenario {
+        id: "same-author-same-year",
+        description: "Two works by the same author in the same year",
+        reference_ids: &["smith_climate_2020", "smith_oceans_2020"],
+    },
+    Scenario {
+        id: "same-surname",
+        description: "Different authors sharing a surname and year",
+        reference_ids: &["miller_j_2019", "miller_a_2019"],
+    },
+    Scenario {
+        id: "et-al-collision",
+        description: "Same first author and year with different co-authors",
+        reference_ids: &["garcia_lee_2021", "garcia_chen_2021"],
+    },
+];
+
+pub struct ScenarioReference {
+    reference: Reference,
+    meta: ReferenceMeta,
+    /// Given name of the first author, for spotting added initials.
+    given: Option<String>,
+}
+
+/// Parses the built-in scenario references. Called once at startup, so a
+/// schema mismatch stops the server instead of failing every request.
+pub fn load_scenarios() -> Result<HashMap<String, ScenarioReference>, serde_yaml::Error> {
+    let typed: HashMap<String, Reference> = serde_yaml::from_str(SCENARIO_YAML)?;
+    let raw: HashMap<String, serde_yaml::Value> = serde_yaml::from_str(SCENARIO_YAML)?;
+
+    Ok(typed.into_iter()
+        .map(|(id, mut reference)| {
+            reference.set_id(id.clone());
+            let value = raw.get(&id)
+                .and_then(|v| serde_json::to_value(v).ok())
+                .unwrap_or(Value::Null);
+            let given = first_given_name(&value);
+            let meta = ReferenceMeta::from_value(&id, &value);
+            (id, ScenarioReference { reference, meta, given })
+        })
+        .collect())
+}
+
+fn first_given_name(value: &Value) -> Option<String> {
+    let author = match value.get("author")? {
+        Value::Array(list) => list.first()?,
+        other => other,
+    };
+    author.get("given").and_then(Value::as_str).map(str::to_string)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// A letter after the year, e.g. "2020a".
+    YearSuffix,
+    /// Initials or given names added to the author.
+    GivenName,
+    /// More names shown before "et al.".
+    AddNames,
+    /// The citation changed, but not in a recognised way.
+    Other,
+}
+
+#[derive(Serialize)]
+pub struct DisambiguatedCitation {
+    pub reference_id: String,
+    /// The citation rendered on its own, with nothing to disambiguate
+    /// against, as escaped HTML.
+    pub baseline: String,
+    /// The citation rendered alongside the rest of its scenario, as escaped
+    /// HTML.
+    pub text: String,
+    pub strategies: Vec<Strategy>,
+}
+
+#[derive(Serialize)]
+pub struct ScenarioReport {
+    pub id: String,
+    pub description: String,
+    pub citations: Vec<DisambiguatedCitation>,
+}
+
+/// Either an intent to generate a style from, or a ready-made style.
+#[derive(Deserialize)]
+pub struct DisambiguationRequest {
+    intent: Option<StyleIntent>,
+    style: Option<Style>,
+}
+
+/// Handler for the `/api/v1/disambiguation` endpoint.
+///
+/// Renders each built-in disambiguation scenario under the given style and
+/// reports which strategy was applied to each citation.
+pub async fn disambiguation_handler(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<DisambiguationRequest>
+) -> Result<Json<Vec<ScenarioReport>>, (StatusCode, String)> {
+    println!("Handling disambiguation request");
+    let style = match (payload.style, payload.intent) {
+        (Some(style), _) => style,
+        (None, Some(intent)) => intent.to_style(),
+        (None, None) => {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, "Either `style` or `intent` is required".to_string()));
+        }
+    };
+
+    // Every scenario renders each reference twice, so keep it off the
+    // executor and count it against the render slots like any preview
+    let permit = state.render_slots.clone().acquire_owned().await
+        .expect("Render semaphore is never closed");
+    tokio::task::spawn_blocking(move || {
+        let reports = SCENARIOS.iter().map(|scenario| render_scenario(&style, scenario, &state.scenarios)).collect();
+        drop(permit);
+        reports
+    })
+    .await
+    .map(Json)
+    .map_err(|e| {
+        println!("Disambiguation render failed: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("Disambiguation render failed: {}", e))
+    })
+}
+
+fn render_scenario(
+    style: &Style,
+    scenario: &Scenario,
+    references: &HashMap<String, ScenarioReference>,
+) -> ScenarioReport {
+    let ids: Vec<String> = scenario.reference_ids.iter()
+        .filter(|id| references.contains_key(**id))
+        .map(|id| id.to_string())
+        .collect();
+
+    let together: Bibliography = ids.iter()
+        .map(|id| (id.clone(), references[id].reference.clone()))
+        .collect();
+    let processor = Processor::new(style.clone(), together);
+
+    let citations = ids.iter()
+        .map(|id| {
+            let scenario_ref = &references[id];
+            let alone: Bibliography = std::iter::once((id.clone(), scenario_ref.reference.clone())).collect();
+            let baseline = render_citation(&Processor::new(style.clone(), alone), id);
+            let text = render_citation(&processor, id);
+            let strategies = detect_strategies(&baseline, &text, &scenario_ref.meta, scenario_ref.given.as_deref());
+            DisambiguatedCitation {
+                reference_id: id.clone(),
+                baseline: html::escape(&baseline),
+                text: html::escape(&text),
+                strategies,
+            }
+        })
+        .collect();
+
+    ScenarioReport {
+        id: scenario.id.to_string(),
+        description: scenario.description.to_string(),
+        citations,
+    }
+}
+
+fn render_citation(processor: &Processor, id: &str) -> String {
+    let citation = Citation {
+        id: Some(format!("disambiguation-{}", id)),
+        items: vec![CitationItem { id: id.to_string(), ..Default::default() }],
+        ..Default::default()
+    };
+    processor.process_citation(&citation).unwrap_or_else(|e| format!("Error: {}", e))
+}
+
+/// Works out what was added to a citation by comparing it with the same
+/// citation rendered in isolation.
+fn detect_strategies(baseline: &str, text: &str, meta: &ReferenceMeta, given: Option<&str>) -> Vec<Strategy> {
+    let baseline = strip_tags(baseline);
+    let text = strip_tags(text);
+    let mut strategies = Vec::new();
+    if baseline == text {
+        return strategies;
+    }
+
+    if let Some(year) = &meta.year {
+        if has_year_suffix(&text, year) && !has_year_suffix(&baseline, year) {
+            strategies.push(Strategy::YearSuffix);
+        }
+    }
+
+    if let Some(given) = given {
+        let initial = given.chars().next().map(|c| format!("{}.", c)).unwrap_or_default();
+        let shows_given = |s: &str| s.contains(given) || (!initial.is_empty() && s.contains(&initial));
+        if shows_given(&text) && !shows_given(&baseline) {
+            strategies.push(Strategy::GivenName);
+        }
+    }
+
+    let names_shown = |s: &str| meta.authors.iter().filter(|a| s.contains(a.as_str())).count();
+    if names_shown(&text) > names_shown(&baseline) {
+        strategies.push(Strategy::AddNames);
+    }
+
+    if strategies.is_empty() {
+        strategies.push(Strategy::Other);
+    }
+    strategies
+}
+
+fn has_year_suffix(text: &str, year: &str) -> bool {
+    text.match_indices(year)
+        .any(|(i, _)| text[i + year.len()..].chars().next().is_some_and(|c| c.is_ascii_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(authors: &[&str], year: &str) -> ReferenceMeta {
+        ReferenceMeta {
+            id: "test".to_string(),
+            authors: authors.iter().map(|a| a.to_string()).collect(),
+            year: Some(year.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn scenario_references_load() {
+        let scenarios = load_scenarios().expect("disambiguation.yaml should match the reference schema");
+        for scenario in &SCENARIOS {
+            for id in scenario.reference_ids {
+                assert!(scenarios.contains_key(*id), "scenario '{}' references missing '{}'", scenario.id, id);
+            }
+        }
+    }
+
+    #[test]
+    fn year_suffix_needs_a_letter_straight_after_the_year() {
+        assert!(has_year_suffix("(Smith 2020a)", "2020"));
+        assert!(has_year_suffix("Smith 2019, 2020b", "2020"));
+        assert!(!has_year_suffix("(Smith 2020)", "2020"));
+        assert!(!has_year_suffix("(Smith 2020 a)", "2020"));
+        assert!(!has_year_suffix("(Smith 2020A)", "2020"));
+        assert!(!has_year_suffix("(Smith)", "2020"));
+    }
+
+    #[test]
+    fn unchanged_citations_need_no_strategy() {
+        let meta = meta(&["Smith"], "2020");
+        assert!(detect_strategies("(Smith 2020)", "<i>(Smith 2020)</i>", &meta, Some("Jane")).is_empty());
+    }
+
+    #[test]
+    fn detects_year_suffix() {
+        let meta = meta(&["Smith"], "2020");
+        assert_eq!(detect_strategies("(Smith 2020)", "(Smith 2020a)", &meta, Some("Jane")), [Strategy::YearSuffix]);
+    }
+
+    #[test]
+    fn detects_given_names_and_initials() {
+        let meta = meta(&["Miller"], "2019");
+        assert_eq!(detect_strategies("(Miller 2019)", "(J. Miller 2019)", &meta, Some("John")), [Strategy::GivenName]);
+        assert_eq!(detect_strategies("(Miller 2019)", "(John Miller 2019)", &meta, Some("John")), [Strategy::GivenName]);
+    }
+
+    #[test]
+    fn detects_added_names() {
+        let meta = meta(&["Garcia", "Lee", "Park"], "2021");
+        assert_eq!(
+            detect_strategies("(Garcia et al. 2021)", "(Garcia, Lee, et al. 2021)", &meta, None),
+            [Strategy::AddNames],
+        );
+    }
+
+    #[test]
+    fn reports_several_strategies_together() {
+        let meta = meta(&["Garcia", "Lee"], "2021");
+        assert_eq!(
+            detect_strategies("(Garcia et al. 2021)", "(M. Garcia, Lee 2021a)", &meta, Some("Maria")),
+            [Strategy::YearSuffix, Strategy::GivenName, Strategy::AddNames],
+        );
+    }
+
+    #[test]
+    fn unrecognised_changes_are_other() {
+        let meta = meta(&["Smith"], "2020");
+        assert_eq!(detect_strategies("(Smith 2020)", "(Smith 2020, p. 4)", &meta, None), [Strategy::Other]);
+    }
+}
@@ -0,0 +1,1108 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use csln_processor::Reference;
+
+/// A reference format accepted by the preview endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReferenceFormat {
+    /// CSLN `Reference` objects, as JSON.
+    Csln,
+    /// CSLN references as YAML text, keyed by id like `comprehensive.yaml`.
+    CslnYaml,
+    CslJson,
+    /// BibTeX or BibLaTeX text.
+    Bibtex,
+    Ris,
+}
+
+/// References as sent by a client: either structured items (CSLN or
+/// CSL-JSON) or the text of a BibTeX, RIS or YAML file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ReferenceInput {
+    Items(Vec<Value>),
+    Text(String),
+}
+
+impl Default for ReferenceInput {
+    fn default() -> Self {
+        ReferenceInput::Items(Vec::new())
+    }
+}
+
+impl ReferenceInput {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            ReferenceInput::Items(items) => items.is_empty(),
+            ReferenceInput::Text(text) => text.trim().is_empty(),
+        }
+    }
+}
+
+/// Something lost or guessed while converting one input entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversionWarning {
+    /// Position of the entry in the input.
+    pub index: usize,
+    pub id: Option<String>,
+    pub message: String,
+}
+
+/// One input entry reshaped into CSLN's reference layout.
+struct Converted {
+    id: Option<String>,
+    value: Value,
+    warnings: Vec<String>,
+}
+
+impl Converted {
+    fn new() -> Self {
+        Converted { id: None, value: Value::Object(Map::new()), warnings: Vec::new() }
+    }
+
+    fn set(&mut self, key: &str, value: Value) {
+        if let Value::Object(map) = &mut self.value {
+            map.insert(key.to_string(), value);
+        }
+    }
+
+    fn parent(&mut self, parent_type: &str, title: Value) {
+        self.set("parent", json!({ "type": parent_type, "title": title }));
+    }
+
+    fn publisher(&mut self, name: Option<String>, location: Option<String>) {
+        let mut publisher = Map::new();
+        if let Some(name) = name {
+            publisher.insert("name".to_string(), Value::String(name));
+        }
+        if let Some(location) = location {
+            publisher.insert("location".to_string(), Value::String(location));
+        }
+        if !publisher.is_empty() {
+            self.set("publisher", Value::Object(publisher));
+        }
+    }
+}
+
+/// Converts client-supplied references into CSLN references.
+///
+/// Entries that can't be converted are skipped with a warning; fields with
+/// no CSLN equivalent are dropped with a warning. When `format` is `None`
+/// it is detected from the input.
+pub fn convert(input: &ReferenceInput, format: Option<ReferenceFormat>) -> (Vec<Reference>, Vec<ConversionWarning>) {
+    let converted: Vec<Result<Converted, String>> = match (input, format) {
+        (ReferenceInput::Items(items), Some(ReferenceFormat::Csln)) => items.iter().map(|v| Ok(from_csln(v))).collect(),
+        (ReferenceInput::Items(items), Some(ReferenceFormat::CslJson)) => items.iter().map(from_csl_json).collect(),
+        // Items may mix both shapes, so detect each one
+        (ReferenceInput::Items(items), _) => items.iter()
+            .map(|v| if looks_like_csl_json(v) { from_csl_json(v) } else { Ok(from_csln(v)) })
+            .collect(),
+        (ReferenceInput::Text(text), format) => match format.unwrap_or_else(|| detect(text)) {
+            ReferenceFormat::Bibtex => parse_bibtex(text),
+            ReferenceFormat::Ris => parse_ris(text),
+            ReferenceFormat::CslJson => match serde_json::from_str::<Value>(text) {
+                Ok(Value::Array(items)) => items.iter().map(from_csl_json).collect(),
+                Ok(item @ Value::Object(_)) => vec![from_csl_json(&item)],
+                Ok(_) => vec![Err("Expected a CSL-JSON object or list of objects".to_string())],
+                Err(e) => vec![Err(format!("Invalid CSL-JSON: {}", e))],
+            },
+            ReferenceFormat::Csln | ReferenceFormat::CslnYaml => parse_csln_yaml(text),
+        },
+    };
+
+    let mut references = Vec::new();
+    let mut warnings = Vec::new();
+    let mut seen = HashSet::new();
+    for (index, entry) in converted.into_iter().enumerate() {
+        let mut entry = match entry {
+            Ok(entry) => entry,
+            Err(message) => {
+                warnings.push(ConversionWarning { index, id: None, message });
+                continue;
+            }
+        };
+
+        let requested = entry.id.take().unwrap_or_else(|| generated_id(&entry.value, index));
+        let id = unique_id(requested.clone(), &mut seen);
+        if id != requested {
+            entry.warnings.push(format!("Duplicate id '{}' renamed to '{}'", requested, id));
+        }
+        warnings.extend(entry.warnings.into_iter()
+            .map(|message| ConversionWarning { index, id: Some(id.clone()), message }));
+
+        match serde_json::from_value::<Reference>(entry.value) {
+            Ok(mut reference) => {
+                reference.set_id(id);
+                references.push(reference);
+            },
+            Err(e) => warnings.push(ConversionWarning {
+                index,
+                id: Some(id),
+                message: format!("Not a valid CSLN reference: {}", e),
+            }),
+        }
+    }
+    (references, warnings)
+}
+
+fn detect(text: &str) -> ReferenceFormat {
+    let text = text.trim_start();
+    if text.starts_with('@') || text.starts_with('%') {
+        ReferenceFormat::Bibtex
+    } else if text.starts_with("TY  -") {
+        ReferenceFormat::Ris
+    } else if text.starts_with('[') {
+        ReferenceFormat::CslJson
+    } else if text.starts_with('{') {
+        // A single CSL-JSON item, or CSLN keyed by id written as JSON
+        match serde_json::from_str::<Value>(text) {
+            Ok(value) if looks_like_csl_json(&value) => ReferenceFormat::CslJson,
+            _ => ReferenceFormat::CslnYaml,
+        }
+    } else {
+        ReferenceFormat::CslnYaml
+    }
+}
+
+/// CSL-JSON gives dates as `date-parts` objects and uses hyphenated names
+/// like `container-title` and types like `article-journal` that CSLN doesn't.
+fn looks_like_csl_json(value: &Value) -> bool {
+    ["issued", "accessed"].iter().any(|key| value.get(key).is_some_and(Value::is_object))
+        || ["container-title", "publisher-place", "page", "DOI", "URL"].iter().any(|key| value.get(key).is_some())
+        || value.get("type").and_then(Value::as_str).is_some_and(|t| CSL_ONLY_TYPES.contains(&t))
+}
+
+fn from_csln(value: &Value) -> Converted {
+    let mut entry = Converted::new();
+    entry.id = value.get("id").and_then(Value::as_str).map(str::to_string);
+    entry.value = value.clone();
+    entry
+}
+
+fn parse_csln_yaml(text: &str) -> Vec<Result<Converted, String>> {
+    let parsed: serde_yaml::Value = match serde_yaml::from_str(text) {
+        Ok(parsed) => parsed,
+        Err(e) => return vec![Err(format!("Invalid CSLN YAML: {}", e))],
+    };
+    let to_json = |value: serde_yaml::Value| serde_json::to_value(value).map_err(|e| e.to_string());
+    match parsed {
+        // A single reference rather than a map of them
+        serde_yaml::Value::Mapping(map) if map.get("type").is_some_and(serde_yaml::Value::is_string) => {
+            vec![to_json(serde_yaml::Value::Mapping(map)).map(|v| from_csln(&v))]
+        },
+        // Keyed by id, as in the corpus files
+        serde_yaml::Value::Mapping(map) => map.into_iter()
+            .map(|(id, value)| {
+                let mut entry = from_csln(&to_json(value)?);
+                entry.id = id.as_str().map(str::to_string).or(entry.id);
+                Ok(entry)
+            })
+            .collect(),
+        serde_yaml::Value::Sequence(list) => list.into_iter()
+            .map(|value| to_json(value).map(|v| from_csln(&v)))
+            .collect(),
+        _ => vec![Err("Expected a map or list of references".to_string())],
+    }
+}
+
+// --- CSL-JSON ---
+
+/// Maps a CSL type to a CSLN type and, for contained works, the type of
+/// the container.
+fn csl_type(csl: &str) -> Option<(&'static str, Option<&'static str>)> {
+    Some(match csl {
+        "book" => ("book", None),
+        "article-journal" | "article" => ("article", Some("academic-journal")),
+        "article-magazine" => ("article", Some("magazine")),
+        "chapter" | "entry-encyclopedia" | "entry-dictionary" => ("chapter", Some("book")),
+        "paper-conference" => ("chapter", Some("proceedings")),
+        "report" => ("report", None),
+        _ => return None,
+    })
+}
+
+/// CSL types whose names CSLN doesn't share.
+const CSL_ONLY_TYPES: [&str; 5] = ["article-journal", "article-magazine", "paper-conference", "entry-encyclopedia", "entry-dictionary"];
+
+const CSL_NAME_FIELDS: [&str; 3] = ["author", "editor", "translator"];
+
+fn from_csl_json(value: &Value) -> Result<Converted, String> {
+    let object = value.as_object().ok_or("Expected a CSL-JSON object")?;
+    let mut entry = Converted::new();
+    entry.id = object.get("id").map(|id| match id {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    });
+
+    let source_type = object.get("type").and_then(Value::as_str).unwrap_or("book");
+    let (csln_type, parent_type) = csl_type(source_type).unwrap_or_else(|| {
+        entry.warnings.push(format!("CSL type '{}' has no CSLN equivalent; treated as a book", source_type));
+        ("book", None)
+    });
+    entry.set("type", json!(csln_type));
+    if source_type == "book" && object.contains_key("editor") && !object.contains_key("author") {
+        entry.set("type", json!("edited-book"));
+    }
+
+    let mut publisher = None;
+    let mut location = None;
+    for (key, field) in object {
+        match key.as_str() {
+            "id" | "type" => {},
+            "title" | "edition" | "note" | "language" => entry.set(key, field.clone()),
+            "title-short" | "citation-key" | "source" | "abstract" => {},
+            "original-title" => entry.set(key, field.clone()),
+            "volume" | "issue" => entry.set(key, numeric(field)),
+            "page" => entry.set("pages", field.clone()),
+            "DOI" | "URL" | "ISBN" | "ISSN" => entry.set(&key.to_lowercase(), field.clone()),
+            "keyword" => entry.set("keywords", json!(field.as_str().map(split_keywords).unwrap_or_default())),
+            "publisher" => publisher = field.as_str().map(str::to_string),
+            "publisher-place" => location = field.as_str().map(str::to_string),
+            "issued" | "accessed" | "original-date" => match csl_date(field) {
+                Some(date) => entry.set(key, json!(date)),
+                None => entry.warnings.push(format!("Could not read the '{}' date", key)),
+            },
+            "container-title" => match parent_type {
+                Some(parent_type) => entry.parent(parent_type, field.clone()),
+                None => entry.warnings.push("Dropped 'container-title': this type has no container".to_string()),
+            },
+            name if CSL_NAME_FIELDS.contains(&name) => entry.set(name, csl_names(field)),
+            other => entry.warnings.push(format!("Dropped unsupported field '{}'", other)),
+        }
+    }
+    entry.publisher(publisher, location);
+    Ok(entry)
+}
+
+fn csl_names(value: &Value) -> Value {
+    let names: Vec<Value> = value.as_array().into_iter().flatten()
+        .map(|name| match name.get("literal") {
+            Some(literal) => json!({ "name": literal }),
+            None => {
+                let mut person = name.clone();
+                if let Value::Object(map) = &mut person {
+                    map.remove("sequence");
+                }
+                person
+            }
+        })
+        .collect();
+    match names.len() {
+        1 => names.into_iter().next().unwrap_or(Value::Null),
+        _ => Value::Array(names),
+    }
+}
+
+/// `{"date-parts": [[2020, 5, 1]]}` -> "2020-05-01". Ranges keep their start.
+fn csl_date(value: &Value) -> Option<String> {
+    if let Some(text) = value.as_str() {
+        return Some(text.to_string());
+    }
+    if let Some(parts) = value.get("date-parts").and_then(|p| p.get(0)).and_then(Value::as_array) {
+        let parts: Vec<i64> = parts.iter()
+            .filter_map(|p| p.as_i64().or_else(|| p.as_str().and_then(|s| s.parse().ok())))
+            .collect();
+        return match parts.as_slice() {
+            [year] => Some(format!("{:04}", year)),
+            [year, month] => Some(format!("{:04}-{:02}", year, month)),
+            [year, month, day, ..] => Some(format!("{:04}-{:02}-{:02}", year, month, day)),
+            [] => None,
+        };
+    }
+    value.get("raw").or_else(|| value.get("literal")).and_then(Value::as_str).map(str::to_string)
+}
+
+/// CSLN gives volumes and issues as numbers where they are numeric.
+fn numeric(value: &Value) -> Value {
+    match value.as_str().and_then(|s| s.trim().parse::<u64>().ok()) {
+        Some(n) => json!(n),
+        None => value.clone(),
+    }
+}
+
+fn split_keywords(text: &str) -> Vec<String> {
+    text.split([',', ';'])
+        .map(|k| k.trim().to_string())
+        .filter(|k| !k.is_empty())
+        .collect()
+}
+
+// --- BibTeX / BibLaTeX ---
+
+fn bibtex_type(entry_type: &str) -> Option<(&'static str, Option<&'static str>)> {
+    Some(match entry_type {
+        "book" | "mvbook" => ("book", None),
+        "collection" | "mvcollection" => ("edited-book", None),
+        "article" => ("article", Some("academic-journal")),
+        "inbook" | "incollection" | "inreference" => ("chapter", Some("book")),
+        "inproceedings" | "conference" => ("chapter", Some("proceedings")),
+        "report" | "techreport" => ("report", None),
+        _ => return None,
+    })
+}
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+struct BibParser {
+    chars: Vec<char>,
+    pos: usize,
+    strings: HashMap<String, String>,
+}
+
+impl BibParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !c.is_whitespace() && !"{}(),=#\"".contains(c)) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            },
+            Some(c) => Err(format!("Expected '{}' but found '{}'", expected, c)),
+            None => Err(format!("Expected '{}' but the input ended", expected)),
+        }
+    }
+
+    /// Reads up to the brace matching the one just consumed.
+    fn braced(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        let mut depth = 1;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(self.chars[start..self.pos - 1].iter().collect());
+                    }
+                },
+                _ => {},
+            }
+        }
+        Err("Unbalanced braces".to_string())
+    }
+
+    fn quoted(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '"' if depth == 0 => return Ok(self.chars[start..self.pos - 1].iter().collect()),
+                _ => {},
+            }
+        }
+        Err("Unterminated quoted value".to_string())
+    }
+
+    /// A field value: braced, quoted, a number or a macro, joined with `#`.
+    fn value(&mut self) -> Result<String, String> {
+        let mut value = String::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('{') => {
+                    self.pos += 1;
+                    value.push_str(&self.braced()?);
+                },
+                Some('"') => {
+                    self.pos += 1;
+                    value.push_str(&self.quoted()?);
+                },
+                _ => {
+                    let word = self.identifier();
+                    if word.is_empty() {
+                        return Err("Missing field value".to_string());
+                    }
+                    let lower = word.to_lowercase();
+                    match self.strings.get(&lower) {
+                        Some(expansion) => value.push_str(expansion),
+                        None => match MONTHS.iter().position(|m| *m == lower) {
+                            Some(i) => value.push_str(&(i + 1).to_string()),
+                            None => value.push_str(&word),
+                        },
+                    }
+                },
+            }
+            self.skip_whitespace();
+            if self.peek() == Some('#') {
+                self.pos += 1;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Reads the fields of an entry up to its closing delimiter.
+    fn fields(&mut self, close: char) -> Result<Vec<(String, String)>, String> {
+        let mut fields = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(c) if c == close => {
+                    self.pos += 1;
+                    return Ok(fields);
+                },
+                Some(_) => {
+                    let name = self.identifier().to_lowercase();
+                    if name.is_empty() {
+                        return Err("Expected a field name".to_string());
+                    }
+                    self.expect('=')?;
+                    fields.push((name, self.value()?));
+                },
+                None => return Err("Entry is not closed".to_string()),
+            }
+        }
+    }
+
+    /// Skips to the next `@` so one malformed entry doesn't lose the rest.
+    fn recover(&mut self) {
+        while self.peek().is_some_and(|c| c != '@') {
+            self.pos += 1;
+        }
+    }
+}
+
+fn parse_bibtex(text: &str) -> Vec<Result<Converted, String>> {
+    let mut parser = BibParser { chars: text.chars().collect(), pos: 0, strings: HashMap::new() };
+    let mut entries = Vec::new();
+
+    loop {
+        parser.recover();
+        if parser.peek().is_none() {
+            break;
+        }
+        parser.pos += 1;
+        let entry_type = parser.identifier().to_lowercase();
+        parser.skip_whitespace();
+        let close = match parser.peek() {
+            Some('{') => '}',
+            Some('(') => ')',
+            // Free text after @comment is itself a comment
+            _ if entry_type == "comment" => continue,
+            _ => {
+                entries.push(Err(format!("Malformed @{} entry", entry_type)));
+                continue;
+            }
+        };
+        parser.pos += 1;
+
+        match entry_type.as_str() {
+            "comment" | "preamble" => {
+                let start = parser.pos;
+                if let Err(e) = parser.braced() {
+                    // Report it and carry on from the next entry
+                    entries.push(Err(format!("Malformed @{}: {}", entry_type, e)));
+                    parser.pos = start;
+                }
+            },
+            "string" => match parser.fields(close) {
+                Ok(fields) => parser.strings.extend(fields),
+                Err(e) => entries.push(Err(format!("Malformed @string: {}", e))),
+            },
+            _ => {
+                parser.skip_whitespace();
+                let key = parser.identifier();
+                let result = parser.fields(close)
+                    .map(|fields| from_bibtex(&entry_type, &key, fields))
+                    .map_err(|e| format!("Malformed @{}{{{}}}: {}", entry_type, key, e));
+                entries.push(result);
+            },
+        }
+    }
+    entries
+}
+
+fn from_bibtex(entry_type: &str, key: &str, fields: Vec<(String, String)>) -> Converted {
+    let mut entry = Converted::new();
+    if !key.is_empty() {
+        entry.id = Some(key.to_string());
+    }
+
+    let (csln_type, parent_type) = bibtex_type(entry_type).unwrap_or_else(|| {
+        entry.warnings.push(format!("@{} has no CSLN equivalent; treated as a book", entry_type));
+        ("book", None)
+    });
+    let fields: HashMap<String, String> = fields.into_iter().collect();
+    let edited = csln_type == "book" && fields.contains_key("editor") && !fields.contains_key("author");
+    entry.set("type", json!(if edited { "edited-book" } else { csln_type }));
+
+    let mut publisher = None;
+    let mut location = None;
+    let mut year = None;
+    let mut month = None;
+    let mut names: Vec<_> = fields.iter().collect();
+    names.sort();
+
+    for (name, raw) in names {
+        if ["author", "editor", "translator"].contains(&name.as_str()) {
+            let names = bibtex_names(raw, &mut entry.warnings, name);
+            entry.set(name, names);
+            continue;
+        }
+        let text = clean_latex(raw, &mut entry.warnings, name);
+        match name.as_str() {
+            "title" | "edition" | "note" | "language" | "isbn" | "issn" | "doi" | "url" => entry.set(name, json!(text)),
+            "volume" => entry.set(name, numeric(&json!(text))),
+            "number" | "issue" => entry.set("issue", numeric(&json!(text))),
+            "pages" => entry.set("pages", json!(text.replace("--", "-"))),
+            "keywords" => entry.set("keywords", json!(split_keywords(&text))),
+            "publisher" | "institution" | "organization" | "school" => publisher = publisher.or(Some(text)),
+            "address" | "location" => location = Some(text),
+            "date" => entry.set("issued", json!(text)),
+            "year" => year = Some(text),
+            "month" => month = text.parse::<u32>().ok(),
+            "journal" | "journaltitle" | "booktitle" => match parent_type {
+                Some(parent_type) => entry.parent(parent_type, json!(text)),
+                None => entry.warnings.push(format!("Dropped '{}': @{} has no container", name, entry_type)),
+            },
+            "abstract" | "file" | "timestamp" | "urldate" | "eprint" | "eprinttype" | "shorttitle" => {},
+            other => entry.warnings.push(format!("Dropped unsupported field '{}'", other)),
+        }
+    }
+
+    if !fields.contains_key("date") {
+        match (year, month) {
+            (Some(year), Some(month)) => entry.set("issued", json!(format!("{}-{:02}", year, month))),
+            (Some(year), None) => entry.set("issued", json!(year)),
+            _ => {},
+        }
+    }
+    entry.publisher(publisher, location);
+    entry
+}
+
+/// Splits a BibTeX name list on " and " outside braces. Names are
+/// "Last, First", "Last, Jr, First" or "First Last"; a fully braced name
+/// is an organisation.
+fn bibtex_names(raw: &str, warnings: &mut Vec<String>, field: &str) -> Value {
+    let mut list = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in raw.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ if depth == 0 && raw[i..].starts_with(" and ") => {
+                list.push(&raw[start..i]);
+                start = i + " and ".len();
+            },
+            _ => {},
+        }
+    }
+    list.push(&raw[start..]);
+
+    let names: Vec<Value> = list.into_iter()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(|raw_name| {
+            let name = clean_latex(raw_name, warnings, field);
+            if raw_name.starts_with('{') && raw_name.ends_with('}') && !raw_name[1..raw_name.len() - 1].contains('}') {
+                return json!({ "name": name });
+            }
+            let name = name.as_str();
+            let parts: Vec<&str> = name.split(',').map(str::trim).collect();
+            match parts.as_slice() {
+                [family, given] => person(family, Some(given), None),
+                [family, suffix, given, ..] => person(family, Some(given), Some(suffix)),
+                _ => match name.rsplit_once(' ') {
+                    Some((given, family)) => person(family, Some(given), None),
+                    None => person(name, None, None),
+                },
+            }
+        })
+        .collect();
+    match names.len() {
+        1 => names.into_iter().next().unwrap_or(Value::Null),
+        _ => Value::Array(names),
+    }
+}
+
+fn person(family: &str, given: Option<&str>, suffix: Option<&str>) -> Value {
+    let mut map = Map::new();
+    map.insert("family".to_string(), json!(family));
+    if let Some(given) = given.filter(|g| !g.is_empty()) {
+        map.insert("given".to_string(), json!(given));
+    }
+    if let Some(suffix) = suffix.filter(|s| !s.is_empty()) {
+        map.insert("suffix".to_string(), json!(suffix));
+    }
+    Value::Object(map)
+}
+
+/// Strips grouping braces and resolves escaped characters and common
+/// accent commands. Any other command is dropped with a warning.
+fn clean_latex(raw: &str, warnings: &mut Vec<String>, field: &str) -> String {
+    let raw = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+    let chars: Vec<char> = raw.chars().collect();
+    let mut out = String::with_capacity(raw.len());
+    let mut unknown = false;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                let command = chars[i + 1];
+                i += 2;
+                match accent(command) {
+                    Some(mark) => {
+                        if chars.get(i) == Some(&'{') {
+                            i += 1;
+                        }
+                        if let Some(&letter) = chars.get(i) {
+                            out.push(letter);
+                            out.push(mark);
+                            i += 1;
+                        }
+                        if chars.get(i) == Some(&'}') {
+                            i += 1;
+                        }
+                    },
+                    None if "&%$#_{}".contains(command) => out.push(command),
+                    // Drop the command name and keep its argument
+                    None => {
+                        unknown = true;
+                        while chars.get(i).is_some_and(|c| c.is_ascii_alphabetic()) {
+                            i += 1;
+                        }
+                    },
+                }
+            },
+            '{' | '}' => i += 1,
+            '~' => {
+                out.push(' ');
+                i += 1;
+            },
+            c => {
+                out.push(c);
+                i += 1;
+            },
+        }
+    }
+    if unknown {
+        warnings.push(format!("'{}' contained LaTeX commands that were removed", field));
+    }
+    out
+}
+
+fn accent(command: char) -> Option<char> {
+    Some(match command {
+        '\'' => '\u{301}',
+        '`' => '\u{300}',
+        '^' => '\u{302}',
+        '"' => '\u{308}',
+        '~' => '\u{303}',
+        '=' => '\u{304}',
+        '.' => '\u{307}',
+        'c' => '\u{327}',
+        _ => return None,
+    })
+}
+
+// --- RIS ---
+
+fn ris_type(tag: &str) -> Option<(&'static str, Option<&'static str>)> {
+    Some(match tag {
+        "BOOK" => ("book", None),
+        "EDBOOK" => ("edited-book", None),
+        "JOUR" | "JFULL" => ("article", Some("academic-journal")),
+        "MGZN" => ("article", Some("magazine")),
+        "CHAP" => ("chapter", Some("book")),
+        "CONF" | "CPAPER" => ("chapter", Some("proceedings")),
+        "RPRT" => ("report", None),
+        _ => return None,
+    })
+}
+
+fn parse_ris(text: &str) -> Vec<Result<Converted, String>> {
+    let mut entries = Vec::new();
+    let mut current: Option<Vec<(String, String)>> = None;
+
+    for line in text.lines() {
+        // Tags are two characters, two spaces, a hyphen and a space
+        let Some((tag, value)) = line.split_once("  -") else { continue };
+        let tag = tag.trim();
+        let value = value.trim().to_string();
+        match tag {
+            "TY" => {
+                if current.is_some() {
+                    entries.push(Err("Entry not closed with ER before the next TY".to_string()));
+                }
+                current = Some(vec![(tag.to_string(), value)]);
+            },
+            "ER" => match current.take() {
+                Some(tags) => entries.push(Ok(from_ris(tags))),
+                None => entries.push(Err("ER without a matching TY".to_string())),
+            },
+            _ => match current.as_mut() {
+                Some(tags) => tags.push((tag.to_string(), value)),
+                None => entries.push(Err(format!("Tag {} outside of an entry", tag))),
+            },
+        }
+    }
+    if current.is_some() {
+        entries.push(Err("Last entry not closed with ER".to_string()));
+    }
+    entries
+}
+
+fn from_ris(tags: Vec<(String, String)>) -> Converted {
+    let mut entry = Converted::new();
+    let source_type = tags.first().map(|(_, v)| v.clone()).unwrap_or_default();
+    let (csln_type, parent_type) = ris_type(&source_type).unwrap_or_else(|| {
+        entry.warnings.push(format!("RIS type '{}' has no CSLN equivalent; treated as a book", source_type));
+        ("book", None)
+    });
+    entry.set("type", json!(csln_type));
+
+    let mut authors = Vec::new();
+    let mut editors = Vec::new();
+    let mut keywords = Vec::new();
+    let mut publisher = None;
+    let mut location = None;
+    let mut start_page = None;
+    let mut end_page = None;
+
+    for (tag, value) in tags.into_iter().skip(1) {
+        match tag.as_str() {
+            "ID" => entry.id = Some(value),
+            "AU" | "A1" => authors.push(ris_name(&value)),
+            "A2" | "ED" if parent_type.is_some() || csln_type == "edited-book" => editors.push(ris_name(&value)),
+            "TI" | "T1" => entry.set("title", json!(value)),
+            "T2" | "JO" | "JF" | "BT" => match parent_type {
+                Some(parent_type) => entry.parent(parent_type, json!(value)),
+                None => entry.warnings.push(format!("Dropped {}: this type has no container", tag)),
+            },
+            "JA" | "J2" => {},
+            "PY" | "Y1" | "DA" => entry.set("issued", json!(ris_date(&value))),
+            "VL" => entry.set("volume", numeric(&json!(value))),
+            "IS" => entry.set("issue", numeric(&json!(value))),
+            "SP" => start_page = Some(value),
+            "EP" => end_page = Some(value),
+            "PB" => publisher = Some(value),
+            "CY" => location = Some(value),
+            "DO" => entry.set("doi", json!(value)),
+            "UR" => entry.set("url", json!(value)),
+            "SN" => entry.set(if csln_type == "article" { "issn" } else { "isbn" }, json!(value)),
+            "ET" => entry.set("edition", json!(value)),
+            "LA" => entry.set("language", json!(value)),
+            "N1" => entry.set("note", json!(value)),
+            "KW" => keywords.push(value),
+            "AB" | "N2" | "L1" | "L2" | "DB" | "DP" | "AN" => {},
+            other => entry.warnings.push(format!("Dropped unsupported tag {}", other)),
+        }
+    }
+
+    for (field, names) in [("author", authors), ("editor", editors)] {
+        match names.len() {
+            0 => {},
+            1 => entry.set(field, names.into_iter().next().unwrap_or(Value::Null)),
+            _ => entry.set(field, Value::Array(names)),
+        }
+    }
+    if !keywords.is_empty() {
+        entry.set("keywords", json!(keywords));
+    }
+    match (start_page, end_page) {
+        (Some(start), Some(end)) => entry.set("pages", json!(format!("{}-{}", start, end))),
+        (Some(start), None) => entry.set("pages", json!(start)),
+        _ => {},
+    }
+    entry.publisher(publisher, location);
+    entry
+}
+
+/// RIS names are "Last, First, Suffix"; a name without a comma is taken
+/// as an organisation.
+fn ris_name(value: &str) -> Value {
+    let parts: Vec<&str> = value.split(',').map(str::trim).collect();
+    match parts.as_slice() {
+        [family, given] => person(family, Some(given), None),
+        [family, given, suffix, ..] => person(family, Some(given), Some(suffix)),
+        _ => json!({ "name": value }),
+    }
+}
+
+/// "2020/05/01/" -> "2020-05-01"
+fn ris_date(value: &str) -> String {
+    value.split('/')
+        .filter(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// --- Ids ---
+
+/// "smith2020" from the first author and year, or "ref-N".
+fn generated_id(value: &Value, index: usize) -> String {
+    let first = match value.get("author").or_else(|| value.get("editor")) {
+        Some(Value::Array(list)) => list.first(),
+        other => other,
+    };
+    let family = first
+        .and_then(|name| name.get("family").or_else(|| name.get("name")))
+        .and_then(Value::as_str)
+        .map(|family| family.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase());
+    let year = value.get("issued")
+        .and_then(Value::as_str)
+        .map(|date| date.chars().take(4).collect::<String>())
+        .unwrap_or_default();
+    match family {
+        Some(family) if !family.is_empty() => format!("{}{}", family, year),
+        _ => format!("ref-{}", index + 1),
+    }
+}
+
+fn unique_id(id: String, seen: &mut HashSet<String>) -> String {
+    let mut candidate = id.clone();
+    let mut n = 2;
+    while !seen.insert(candidate.clone()) {
+        candidate = format!("{}-{}", id, n);
+        n += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(entries: Vec<Result<Converted, String>>) -> Vec<Value> {
+        entries.into_iter().map(|e| e.expect("entry should convert").value).collect()
+    }
+
+    fn ids(text: &str) -> Vec<Option<String>> {
+        let (references, _) = convert(&ReferenceInput::Text(text.to_string()), None);
+        references.iter().map(|r| r.id().clone()).collect()
+    }
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(detect("@book{a, title={T}}"), ReferenceFormat::Bibtex);
+        assert_eq!(detect("TY  - BOOK\nER  -"), ReferenceFormat::Ris);
+        assert_eq!(detect(r#"[{"id": "a", "type": "book"}]"#), ReferenceFormat::CslJson);
+        assert_eq!(detect("a:\n  type: book\n"), ReferenceFormat::CslnYaml);
+    }
+
+    #[test]
+    fn single_csl_json_object_is_one_reference() {
+        let text = r#"{"id": "doe2020", "type": "article-journal", "title": "On Things"}"#;
+        assert_eq!(detect(text), ReferenceFormat::CslJson);
+        assert_eq!(ids(text), [Some("doe2020".to_string())]);
+
+        let text = r#"{"id": "doe2020", "type": "book", "issued": {"date-parts": [[2020]]}}"#;
+        assert_eq!(detect(text), ReferenceFormat::CslJson);
+        assert_eq!(ids(text), [Some("doe2020".to_string())]);
+    }
+
+    #[test]
+    fn csln_keyed_by_id_written_as_json_stays_csln() {
+        let text = r#"{"doe2020": {"type": "book", "title": "On Things"}}"#;
+        assert_eq!(detect(text), ReferenceFormat::CslnYaml);
+        assert_eq!(ids(text), [Some("doe2020".to_string())]);
+    }
+
+    #[test]
+    fn single_csln_reference_is_not_read_as_a_map() {
+        let entries = values(parse_csln_yaml("id: doe2020\ntype: book\ntitle: On Things\n"));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["title"], "On Things");
+    }
+
+    #[test]
+    fn converts_csl_json() {
+        let item = json!({
+            "id": 7,
+            "type": "article-journal",
+            "title": "Attention",
+            "container-title": "Journal of Things",
+            "volume": "12",
+            "page": "1-10",
+            "DOI": "10.1/x",
+            "issued": {"date-parts": [[2017, 6]]},
+            "author": [{"family": "Vaswani", "given": "Ashish", "sequence": "first"}, {"literal": "Google Brain"}],
+            "publisher": "Press",
+            "publisher-place": "Boston",
+            "genre": "Research",
+        });
+        let entry = from_csl_json(&item).unwrap();
+        assert_eq!(entry.id.as_deref(), Some("7"));
+        assert_eq!(entry.value, json!({
+            "type": "article",
+            "title": "Attention",
+            "parent": {"type": "academic-journal", "title": "Journal of Things"},
+            "volume": 12,
+            "pages": "1-10",
+            "doi": "10.1/x",
+            "issued": "2017-06",
+            "author": [{"family": "Vaswani", "given": "Ashish"}, {"name": "Google Brain"}],
+            "publisher": {"name": "Press", "location": "Boston"},
+        }));
+        assert_eq!(entry.warnings, ["Dropped unsupported field 'genre'"]);
+    }
+
+    #[test]
+    fn malformed_csl_json_is_reported() {
+        let (references, warnings) = convert(&ReferenceInput::Text("[{\"id\": ".to_string()), Some(ReferenceFormat::CslJson));
+        assert!(references.is_empty());
+        assert!(warnings[0].message.starts_with("Invalid CSL-JSON"));
+
+        assert!(from_csl_json(&json!("not an object")).is_err());
+        let entry = from_csl_json(&json!({"type": "song", "issued": {"season": 2}})).unwrap();
+        assert_eq!(entry.warnings, [
+            "CSL type 'song' has no CSLN equivalent; treated as a book",
+            "Could not read the 'issued' date",
+        ]);
+    }
+
+    #[test]
+    fn converts_bibtex() {
+        let text = r#"
+            @string{pup = "Princeton University Press"}
+            @article{einstein1905,
+                author = {Einstein, Albert},
+                title = {Zur Elektrodynamik bewegter K{\"o}rper},
+                journal = {Annalen der Physik},
+                year = 1905, month = jun,
+                volume = {17}, number = "10",
+                pages = {891--921},
+            }
+            @book{princeton, editor = "Doe, Jane", title = "Collected", publisher = pup}
+        "#;
+        let entries = values(parse_bibtex(text));
+        assert_eq!(entries[0], json!({
+            "type": "article",
+            "author": {"family": "Einstein", "given": "Albert"},
+            "title": "Zur Elektrodynamik bewegter Ko\u{308}rper",
+            "parent": {"type": "academic-journal", "title": "Annalen der Physik"},
+            "issued": "1905-06",
+            "volume": 17,
+            "issue": 10,
+            "pages": "891-921",
+        }));
+        assert_eq!(entries[1]["type"], "edited-book");
+        assert_eq!(entries[1]["publisher"], json!({"name": "Princeton University Press"}));
+        assert_eq!(ids(text), [Some("einstein1905".to_string()), Some("princeton".to_string())]);
+    }
+
+    #[test]
+    fn parses_bibtex_names() {
+        let mut warnings = Vec::new();
+        let names = bibtex_names("Doe, Jr, John and Jane Roe and {World Health Organization}", &mut warnings, "author");
+        assert_eq!(names, json!([
+            {"family": "Doe", "given": "John", "suffix": "Jr"},
+            {"family": "Roe", "given": "Jane"},
+            {"name": "World Health Organization"},
+        ]));
+        assert_eq!(bibtex_names("Plato", &mut warnings, "author"), json!({"family": "Plato"}));
+        // " and " inside braces is part of a name
+        assert_eq!(bibtex_names("{Smith and Sons}", &mut warnings, "author"), json!({"name": "Smith and Sons"}));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn malformed_bibtex_entries_do_not_lose_the_rest() {
+        let text = "@article{broken, title = }\n@book{ok, title = {Fine}}";
+        let entries = parse_bibtex(text);
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].as_ref().is_err_and(|e| e.starts_with("Malformed @article{broken}")));
+        assert_eq!(entries[1].as_ref().unwrap().value["title"], "Fine");
+    }
+
+    #[test]
+    fn malformed_comments_are_reported() {
+        let text = "@comment{unclosed\n@book{ok, title = {Fine}}";
+        let entries = parse_bibtex(text);
+        assert!(entries[0].as_ref().is_err_and(|e| e.starts_with("Malformed @comment")));
+        assert_eq!(entries[1].as_ref().unwrap().value["title"], "Fine");
+
+        // Free-text comments and well-formed ones are skipped quietly
+        let entries = parse_bibtex("@comment jabref-meta\n@comment{x}\n@book{ok, title = {Fine}}");
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].is_ok());
+    }
+
+    #[test]
+    fn cleans_latex() {
+        let mut warnings = Vec::new();
+        assert_eq!(clean_latex(r"Caf\'{e} \& {Bar}~50\%", &mut warnings, "title"), "Cafe\u{301} & Bar 50%");
+        assert!(warnings.is_empty());
+        assert_eq!(clean_latex(r"\emph{Very} important", &mut warnings, "title"), "Very important");
+        assert_eq!(warnings, ["'title' contained LaTeX commands that were removed"]);
+    }
+
+    #[test]
+    fn converts_ris() {
+        let text = "TY  - JOUR\nID  - smith2020\nAU  - Smith, John\nAU  - Acme Corp\nTI  - Results\nJO  - Journal\n\
+                    PY  - 2020/05/01/\nSP  - 10\nEP  - 20\nSN  - 1234-5678\nKW  - one\nKW  - two\nXY  - odd\nER  - \n";
+        let entries = parse_ris(text);
+        let entry = entries[0].as_ref().unwrap();
+        assert_eq!(entry.id.as_deref(), Some("smith2020"));
+        assert_eq!(entry.value, json!({
+            "type": "article",
+            "title": "Results",
+            "parent": {"type": "academic-journal", "title": "Journal"},
+            "issued": "2020-05-01",
+            "pages": "10-20",
+            "issn": "1234-5678",
+            "author": [{"family": "Smith", "given": "John"}, {"name": "Acme Corp"}],
+            "keywords": ["one", "two"],
+        }));
+        assert_eq!(entry.warnings, ["Dropped unsupported tag XY"]);
+    }
+
+    #[test]
+    fn parses_ris_names() {
+        assert_eq!(ris_name("Doe, Jane, III"), json!({"family": "Doe", "given": "Jane", "suffix": "III"}));
+        assert_eq!(ris_name("UNESCO"), json!({"name": "UNESCO"}));
+    }
+
+    #[test]
+    fn malformed_ris_is_reported() {
+        let entries = parse_ris("AU  - Stray, Tag\nTY  - BOOK\nTY  - BOOK\nTI  - Kept\nER  - \nER  - \nTY  - BOOK\n");
+        let errors: Vec<&String> = entries.iter().filter_map(|e| e.as_ref().err()).collect();
+        assert_eq!(errors, [
+            "Tag AU outside of an entry",
+            "Entry not closed with ER before the next TY",
+            "ER without a matching TY",
+            "Last entry not closed with ER",
+        ]);
+        assert_eq!(entries.iter().filter(|e| e.is_ok()).count(), 1);
+    }
+
+    #[test]
+    fn generates_and_deduplicates_ids() {
+        let items = vec![
+            json!({"type": "book", "author": {"family": "O'Brien"}, "issued": "1999"}),
+            json!({"type": "book", "author": [{"family": "O'Brien"}], "issued": "1999-03"}),
+            json!({"type": "book", "title": "Anonymous"}),
+        ];
+        let (references, warnings) = convert(&ReferenceInput::Items(items), Some(ReferenceFormat::Csln));
+        let ids: Vec<&str> = references.iter().filter_map(|r| r.id().as_deref()).collect();
+        assert_eq!(ids, ["obrien1999", "obrien1999-2", "ref-3"]);
+        assert_eq!(warnings[0].message, "Duplicate id 'obrien1999' renamed to 'obrien1999-2'");
+    }
+}
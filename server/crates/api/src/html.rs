@@ -0,0 +1,451 @@
+use serde::Serialize;
+use serde_json::Value;
+use intent_engine::{CitationClass, PreviewContext, PreviewFragment};
+
+use crate::corpus::ReferenceMeta;
+
+/// Escapes text for inclusion in HTML element content or attribute values.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Collects the fragments annotated while rendering one part of a preview,
+/// and tags their spans with the intent field that controls them.
+pub struct Trace {
+    pub class: CitationClass,
+    pub context: PreviewContext,
+    pub fragments: Vec<PreviewFragment>,
+}
+
+impl Trace {
+    pub fn new(class: CitationClass, context: PreviewContext) -> Self {
+        Trace { class, context, fragments: Vec::new() }
+    }
+
+    fn record(&mut self, component: &str, reference_id: Option<&str>, text: &str) -> String {
+        let intent_field = self.class.intent_field_for(component, self.context);
+        self.fragments.push(PreviewFragment {
+            context: self.context,
+            text: text.to_string(),
+            component: component.to_string(),
+            reference_id: reference_id.map(str::to_string),
+            intent_field: intent_field.map(str::to_string),
+        });
+        let id_attr = reference_id
+            .map(|id| format!(" data-reference-id=\"{}\"", escape(id)))
+            .unwrap_or_default();
+        let field_attr = intent_field
+            .map(|field| format!(" data-intent-field=\"{}\"", field))
+            .unwrap_or_default();
+        format!("data-component=\"{}\"{}{}", escape(component), id_attr, field_attr)
+    }
+}
+
+/// Template component kinds, in the order they are looked for in a
+/// serialized CSLN template component.
+const COMPONENT_KINDS: [&str; 6] = ["contributor", "date", "title", "number", "variable", "list"];
+
+/// One rendered piece of a processed entry, with the template component
+/// that produced it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    /// CSLN template component path, e.g. "contributor:author".
+    pub path: String,
+    /// The text the component rendered, without affixes.
+    pub value: String,
+    /// Whether the component's rendering asks for emphasis.
+    pub italic: bool,
+}
+
+impl Component {
+    /// Reads the components of an entry from the processor's output.
+    ///
+    /// Entries are read through their serialized form, a list of
+    /// `{ template_component, value }` objects, so only the parts needed
+    /// for annotation are relied on.
+    pub fn from_entry(entry: &impl Serialize) -> Vec<Component> {
+        let value = serde_json::to_value(entry).unwrap_or(Value::Null);
+        value.as_array()
+            .map(|items| items.iter().filter_map(Component::from_value).collect())
+            .unwrap_or_default()
+    }
+
+    fn from_value(item: &Value) -> Option<Component> {
+        let template = item.get("template_component")?;
+        // Externally tagged enums nest the component one level down
+        let template = match template.as_object() {
+            Some(obj) if obj.len() == 1 && obj.values().all(Value::is_object) => obj.values().next()?,
+            _ => template,
+        };
+        let path = COMPONENT_KINDS.iter()
+            .find_map(|kind| {
+                let variable = template.get(*kind)?;
+                let variable = variable.as_str().map(str::to_string).unwrap_or_default();
+                Some(format!("{}:{}", kind, variable))
+            })?;
+        let value = item.get("value")
+            .or_else(|| item.get("values").and_then(|v| v.get("value")))
+            .and_then(Value::as_str)?
+            .to_string();
+        let emph = |v: &Value| v.get("emph").and_then(Value::as_bool).unwrap_or(false);
+        let italic = emph(template) || template.get("rendering").is_some_and(emph);
+        Some(Component { path, value, italic })
+    }
+
+    /// The CSS class for the component, e.g. "csl-author" or "csl-title".
+    fn class(&self) -> String {
+        let (kind, variable) = self.path.split_once(':').unwrap_or((&self.path, ""));
+        match kind {
+            "title" => "csl-title".to_string(),
+            _ if !variable.is_empty() => format!("csl-{}", variable),
+            _ => format!("csl-{}", kind),
+        }
+    }
+}
+
+/// Escapes a processed entry and wraps each of its components in a
+/// `<span class="csl-…">`, using `<i>` for emphasised ones and tagging
+/// titles in another language with `lang`.
+///
+/// Components are located in order in the rendered text, so each span's
+/// `data-component` names the template component that produced it.
+/// Components whose text no longer appears (e.g. an author list replaced
+/// by a substitute) are left unmarked.
+pub fn annotate_components(
+    text: &str,
+    components: &[Component],
+    meta: &ReferenceMeta,
+    trace: &mut Trace,
+) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut pos = 0;
+    for component in components {
+        if component.value.is_empty() {
+            continue;
+        }
+        let Some(offset) = text[pos..].find(&component.value) else {
+            continue;
+        };
+        let start = pos + offset;
+        let end = start + component.value.len();
+        let attrs = trace.record(&component.path, Some(&meta.id), &component.value);
+        let lang = if component.path.starts_with("title:") { lang_attr(meta) } else { String::new() };
+        out.push_str(&escape(&text[pos..start]));
+        out.push_str(&format!("<span class=\"{}\" {}{}>", component.class(), attrs, lang));
+        if component.italic {
+            out.push_str(&format!("<i>{}</i>", escape(&component.value)));
+        } else {
+            out.push_str(&escape(&component.value));
+        }
+        out.push_str("</span>");
+        pos = end;
+    }
+    out.push_str(&escape(&text[pos..]));
+    out
+}
+
+/// ` lang="…"` for references not in English, which the preview is set in.
+fn lang_attr(meta: &ReferenceMeta) -> String {
+    meta.language.as_deref()
+        .filter(|lang| !lang.starts_with("en"))
+        .map(|lang| format!(" lang=\"{}\"", escape(lang)))
+        .unwrap_or_default()
+}
+
+/// A recognised field within rendered text.
+struct Span<'a> {
+    start: usize,
+    end: usize,
+    class: &'static str,
+    component: &'static str,
+    reference_id: &'a str,
+    lang: String,
+}
+
+/// Escapes rendered text and wraps the fields it can recognise from the
+/// references' metadata in `<span class="csl-…">` elements.
+///
+/// Citations come back from the processor as flat strings, so fields are
+/// located by matching the reference's title, names and year, plus the
+/// `locator` cited for the first reference; bibliography entries carry
+/// their components and use [`annotate_components`] instead.
+///
+/// Each span carries a `data-component` attribute naming the CSLN template
+/// component that renders that field (e.g. `contributor:author`) and, where
+/// one applies, a `data-intent-field` attribute naming the `StyleIntent`
+/// field behind it.
+pub fn annotate(text: &str, metas: &[&ReferenceMeta], locator: Option<&str>, trace: &mut Trace) -> String {
+    let lower = text.to_lowercase();
+    // Lowercasing can change byte lengths for some scripts; only trust
+    // case-insensitive offsets when it didn't
+    let can_fold = lower.len() == text.len();
+    let mut spans: Vec<Span> = Vec::new();
+
+    for meta in metas {
+        if let Some(title) = &meta.title {
+            let needle = if can_fold { title.to_lowercase() } else { title.clone() };
+            let haystack = if can_fold { lower.as_str() } else { text };
+            if let Some(start) = haystack.find(&needle) {
+                let lang = lang_attr(meta);
+                spans.push(Span {
+                    start,
+                    end: start + needle.len(),
+                    class: "csl-title",
+                    component: "title:primary",
+                    reference_id: &meta.id,
+                    lang,
+                });
+            }
+        }
+
+        for author in &meta.authors {
+            if let Some(start) = text.find(author.as_str()) {
+                spans.push(Span {
+                    start,
+                    end: start + author.len(),
+                    class: "csl-author",
+                    component: "contributor:author",
+                    reference_id: &meta.id,
+                    lang: String::new(),
+                });
+            }
+        }
+
+        if let Some(year) = &meta.year {
+            if let Some(start) = text.find(year.as_str()) {
+                spans.push(Span {
+                    start,
+                    end: start + year.len(),
+                    class: "csl-issued",
+                    component: "date:issued",
+                    reference_id: &meta.id,
+                    lang: String::new(),
+                });
+            }
+        }
+    }
+
+    if let (Some(locator), Some(meta)) = (locator, metas.first()) {
+        if let Some((start, end)) = find_locator(text, locator) {
+            spans.push(Span {
+                start,
+                end,
+                class: "csl-locator",
+                component: "locator",
+                reference_id: &meta.id,
+                lang: String::new(),
+            });
+        }
+    }
+
+    // Keep the earliest of any overlapping spans
+    spans.sort_by_key(|span| (span.start, std::cmp::Reverse(span.end)));
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut pos = 0;
+    for span in spans {
+        if span.start < pos || !text.is_char_boundary(span.start) || !text.is_char_boundary(span.end) {
+            continue;
+        }
+        let content = &text[span.start..span.end];
+        let attrs = trace.record(span.component, Some(span.reference_id), content);
+        out.push_str(&escape(&text[pos..span.start]));
+        out.push_str(&format!("<span class=\"{}\" {}{}>", span.class, attrs, span.lang));
+        out.push_str(&escape(content));
+        out.push_str("</span>");
+        pos = span.end;
+    }
+    out.push_str(&escape(&text[pos..]));
+    out
+}
+
+/// Finds a locator standing on its own (not inside a longer number),
+/// together with the punctuation that introduces it, e.g. ": 34" or
+/// ", p. 34", so the whole piece the citation preset controls is marked.
+fn find_locator(text: &str, locator: &str) -> Option<(usize, usize)> {
+    const LEADS: [&str; 5] = [", p. ", " p. ", ": ", ", ", ":"];
+    let is_digit_at = |i: usize| text[i..].chars().next().is_some_and(|c| c.is_ascii_digit());
+    text.match_indices(locator)
+        .map(|(start, _)| (start, start + locator.len()))
+        .find(|&(start, end)| {
+            let before = text[..start].chars().next_back().is_some_and(|c| c.is_ascii_digit());
+            !before && (end == text.len() || !is_digit_at(end))
+        })
+        .map(|(start, end)| {
+            let lead = LEADS.iter().find(|lead| text[..start].ends_with(*lead)).map_or(0, |lead| lead.len());
+            (start - lead, end)
+        })
+}
+
+/// An in-text citation, wrapped in `<cite>`.
+pub fn citation(text: &str, metas: &[&ReferenceMeta], locator: Option<&str>, trace: &mut Trace) -> String {
+    format!("<cite class=\"csl-citation\">{}</cite>", annotate(text, metas, locator, trace))
+}
+
+/// A single bibliography entry, annotated from its components when the
+/// processor provided them.
+pub fn bibliography_entry(text: &str, components: &[Component], meta: &ReferenceMeta, trace: &mut Trace) -> String {
+    let body = if components.is_empty() {
+        annotate(text, &[meta], None, trace)
+    } else {
+        annotate_components(text, components, meta, trace)
+    };
+    format!(
+        "<div class=\"csl-entry bib-entry\" role=\"listitem\" data-reference-id=\"{}\">{}</div>",
+        escape(&meta.id),
+        body
+    )
+}
+
+/// A bibliography group heading.
+pub fn group_heading(heading: &str, trace: &mut Trace) -> String {
+    let attrs = trace.record("group:heading", None, heading);
+    format!("<h4 class=\"csl-bib-group\" {}>{}</h4>", attrs, escape(heading))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn austen() -> ReferenceMeta {
+        ReferenceMeta {
+            id: "austen_pride".to_string(),
+            ref_type: "book".to_string(),
+            title: Some("Pride and Prejudice".to_string()),
+            authors: vec!["Austen".to_string()],
+            year: Some("1813".to_string()),
+            language: Some("en".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn einstein() -> ReferenceMeta {
+        ReferenceMeta {
+            id: "einstein<1>".to_string(),
+            title: Some("Zur Elektrodynamik".to_string()),
+            authors: vec!["Einstein".to_string()],
+            year: Some("1905".to_string()),
+            language: Some("de".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn trace(context: PreviewContext) -> Trace {
+        Trace::new(CitationClass::AuthorDate, context)
+    }
+
+    fn component(path: &str, value: &str, italic: bool) -> Component {
+        Component { path: path.to_string(), value: value.to_string(), italic }
+    }
+
+    #[test]
+    fn escapes_markup_and_quotes() {
+        assert_eq!(escape(r#"<b>"Tom" & 'Jerry'</b>"#), "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;");
+        assert_eq!(escape("plain – text"), "plain – text");
+    }
+
+    #[test]
+    fn annotate_escapes_text_around_and_inside_spans() {
+        let mut meta = austen();
+        meta.title = Some("Pride & Prejudice".to_string());
+        let mut trace = trace(PreviewContext::Citation);
+        let html = annotate("<Austen> 1813, Pride & Prejudice", &[&meta], None, &mut trace);
+        assert!(html.starts_with("&lt;<span class=\"csl-author\""));
+        assert!(html.contains(">Pride &amp; Prejudice</span>"));
+        assert!(!html.contains("<Austen>"));
+    }
+
+    #[test]
+    fn annotate_records_fragments_with_intent_fields() {
+        let mut trace = trace(PreviewContext::Citation);
+        annotate("(Austen 1813)", &[&austen()], None, &mut trace);
+        let found: Vec<(&str, Option<&str>)> = trace.fragments.iter()
+            .map(|f| (f.component.as_str(), f.intent_field.as_deref()))
+            .collect();
+        assert_eq!(found, [
+            ("contributor:author", Some("author_format")),
+            ("date:issued", Some("citation_preset")),
+        ]);
+    }
+
+    #[test]
+    fn components_are_wrapped_in_order_with_italics_and_lang() {
+        let components = [
+            component("contributor:author", "Einstein, A.", false),
+            component("date:issued", "1905", false),
+            component("title:primary", "Zur Elektrodynamik", true),
+        ];
+        let mut trace = trace(PreviewContext::Bibliography);
+        let html = annotate_components("Einstein, A. (1905). Zur Elektrodynamik.", &components, &einstein(), &mut trace);
+        assert_eq!(html, concat!(
+            "<span class=\"csl-author\" data-component=\"contributor:author\" data-reference-id=\"einstein&lt;1&gt;\" data-intent-field=\"author_format\">Einstein, A.</span>",
+            " (<span class=\"csl-issued\" data-component=\"date:issued\" data-reference-id=\"einstein&lt;1&gt;\" data-intent-field=\"bibliography_preset\">1905</span>). ",
+            "<span class=\"csl-title\" data-component=\"title:primary\" data-reference-id=\"einstein&lt;1&gt;\" data-intent-field=\"bibliography_preset\" lang=\"de\"><i>Zur Elektrodynamik</i></span>.",
+        ));
+        assert_eq!(trace.fragments.len(), 3);
+    }
+
+    #[test]
+    fn components_missing_from_the_text_are_skipped() {
+        let components = [
+            component("contributor:author", "Austen, J.", false),
+            component("title:primary", "Pride <and> Prejudice", false),
+        ];
+        let mut trace = trace(PreviewContext::Bibliography);
+        let html = annotate_components("———. Pride <and> Prejudice.", &components, &austen(), &mut trace);
+        assert!(html.starts_with("———. <span class=\"csl-title\""));
+        assert!(html.contains(">Pride &lt;and&gt; Prejudice</span>."));
+        assert_eq!(trace.fragments.len(), 1);
+    }
+
+    #[test]
+    fn reads_components_from_processor_output() {
+        let entry = json!([
+            { "template_component": { "contributor": "author", "form": "long" }, "value": "Austen, J." },
+            { "template_component": { "title": "primary", "rendering": { "emph": true } }, "value": "Pride and Prejudice" },
+            { "template_component": { "Date": { "date": "issued", "emph": false } }, "value": "1813" },
+            { "template_component": { "unknown": "x" }, "value": "ignored" },
+        ]);
+        assert_eq!(Component::from_entry(&entry), [
+            component("contributor:author", "Austen, J.", false),
+            component("title:primary", "Pride and Prejudice", true),
+            component("date:issued", "1813", false),
+        ]);
+        assert!(Component::from_entry(&"not an entry").is_empty());
+    }
+
+    #[test]
+    fn bibliography_entries_fall_back_to_metadata() {
+        let mut trace = trace(PreviewContext::Bibliography);
+        let html = bibliography_entry("Austen. 1813. Pride and Prejudice.", &[], &austen(), &mut trace);
+        assert!(html.contains("class=\"csl-author\""));
+        assert!(html.contains("class=\"csl-title\""));
+    }
+
+    #[test]
+    fn marks_the_locator_with_its_punctuation() {
+        let mut trace = trace(PreviewContext::Citation);
+        let html = annotate("(Austen 1813: 34)", &[&austen()], Some("34"), &mut trace);
+        assert!(html.contains("1813</span><span class=\"csl-locator\" data-component=\"locator\""));
+        assert!(html.contains("data-intent-field=\"citation_preset\">: 34</span>)"));
+        assert_eq!(trace.fragments.last().unwrap().text, ": 34");
+    }
+
+    #[test]
+    fn locators_inside_longer_numbers_are_ignored() {
+        assert_eq!(find_locator("(Smith 1934, 341)", "34"), None);
+        assert_eq!(find_locator("(Smith 1934, p. 34)", "34"), Some((11, 18)));
+        assert_eq!(find_locator("[1] 34", "34"), Some((4, 6)));
+    }
+}
@@ -1,20 +1,49 @@
 use axum::{
-    extract::State,
-    routing::{get, post},
+    extract::{Query, State},
+    routing::{get, patch, post},
     Router,
     Json,
 };
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::Semaphore;
 use serde_json::{Value, json};
 use csln_core::Style;
 use csln_processor::{Processor, Reference, Bibliography, Citation, CitationItem};
 use serde::{Deserialize, Serialize};
-use intent_engine::{StyleIntent, DecisionPackage};
+use intent_engine::{StyleIntent, StyleIntentPatch, DecisionPackage, NotePreview, PreviewContext, PreviewFragment};
+
+/// Longest a caller waits for a single preview render, including time spent
+/// queued for a render slot.
+const RENDER_TIMEOUT: Duration = Duration::from_secs(2);
+
+mod bibliography;
+mod cache;
+mod corpus;
+mod disambiguation;
+mod formats;
+mod html;
+mod notes;
+mod stream;
+mod stress;
+
+use bibliography::BibliographyOptions;
+use cache::RenderCache;
+
+use corpus::{ReferenceFilter, ReferenceMeta};
+use formats::{ConversionWarning, ReferenceFormat, ReferenceInput};
 
 struct AppState {
     references: HashMap<String, Reference>,
+    /// Type, keywords and other loosely-shaped fields for each reference.
+    metadata: HashMap<String, ReferenceMeta>,
+    preview_cache: RenderCache<PreviewSet>,
+    /// Limits how many preview renders run on the blocking pool at once.
+    render_slots: Arc<Semaphore>,
+    /// References for the built-in disambiguation scenarios.
+    scenarios: HashMap<String, disambiguation::ScenarioReference>,
 }
 
 #[tokio::main]
@@ -39,28 +68,60 @@ async fn main() {
     
     println!("Loading references from: {}", ref_path);
 
-    let references: HashMap<String, Reference> = match std::fs::File::open(ref_path) {
-        Ok(f) => match serde_yaml::from_reader::<_, HashMap<String, Reference>>(f) {
-            Ok(mut refs) => {
-                // Ensure each reference has its ID set from the map key
-                for (id, reference) in refs.iter_mut() {
-                    reference.set_id(id.clone());
-                }
-                refs
-            },
-            Err(e) => {
-                println!("Failed to parse comprehensive.yaml: {}", e);
-                HashMap::new()
+    let raw = match std::fs::read_to_string(ref_path) {
+        Ok(raw) => raw,
+        Err(e) => {
+            println!("Failed to open comprehensive.yaml: {}", e);
+            String::new()
+        }
+    };
+
+    let references: HashMap<String, Reference> = match serde_yaml::from_str::<HashMap<String, Reference>>(&raw) {
+        Ok(mut refs) => {
+            // Ensure each reference has its ID set from the map key
+            for (id, reference) in refs.iter_mut() {
+                reference.set_id(id.clone());
             }
+            refs
         },
         Err(e) => {
-            println!("Failed to open comprehensive.yaml: {}", e);
+            println!("Failed to parse comprehensive.yaml: {}", e);
             HashMap::new()
         }
     };
 
+    // Keywords and other metadata are read from the raw document, since the
+    // typed references don't necessarily round-trip them
+    let metadata: HashMap<String, ReferenceMeta> = serde_yaml::from_str::<HashMap<String, serde_yaml::Value>>(&raw)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(id, value)| {
+            let value = serde_json::to_value(value).ok()?;
+            let meta = ReferenceMeta::from_value(&id, &value);
+            Some((id, meta))
+        })
+        .collect();
+
+    let cache_capacity = std::env::var("PREVIEW_CACHE_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(cache::DEFAULT_CAPACITY);
+
+    let scenarios = disambiguation::load_scenarios()
+        .expect("Failed to parse disambiguation.yaml");
+
+    let render_slots = std::env::var("PREVIEW_RENDER_SLOTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(4);
+
     let state = Arc::new(AppState {
-        references: references.clone()
+        references: references.clone(),
+        metadata,
+        preview_cache: RenderCache::new(cache_capacity),
+        render_slots: Arc::new(Semaphore::new(render_slots)),
+        scenarios,
     });
     
     println!("Loaded {} references.", references.len());
@@ -73,7 +134,13 @@ async fn main() {
         .route("/preview/bibliography", post(preview_bibliography))
         .route("/api/v1/decide", post(decide_handler))
         .route("/api/v1/preview", post(preview_set_handler))
+        .route("/api/v1/preview/stream", get(stream::preview_stream_handler))
         .route("/api/v1/generate", post(generate_handler))
+        .route("/api/v1/export/csl", post(export_csl_handler))
+        .route("/api/v1/intent", patch(patch_intent_handler))
+        .route("/api/v1/stress-test", post(stress::stress_test_handler))
+        .route("/api/v1/disambiguation", post(disambiguation::disambiguation_handler))
+        .route("/api/v1/cache/stats", get(cache_stats))
         .with_state(state)
         .layer(tower_http::cors::CorsLayer::permissive());
 
@@ -95,25 +162,65 @@ async fn version() -> Json<Value> {
     }))
 }
 
-async fn get_references(State(state): State<Arc<AppState>>) -> Json<HashMap<String, Reference>> {
-    Json(state.references.clone())
+async fn get_references(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<ReferenceFilter>,
+) -> Json<HashMap<String, Reference>> {
+    let references = state.references.iter()
+        .filter(|(id, _)| state.metadata.get(*id).is_some_and(|meta| filter.matches(meta)))
+        .map(|(id, r)| (id.clone(), r.clone()))
+        .collect();
+    Json(references)
 }
 
 #[derive(Deserialize)]
 struct PreviewRequest {
     style: Style,
-    references: Vec<Reference>,
+    /// CSLN or CSL-JSON objects, or the text of a BibTeX, RIS or CSLN YAML file.
+    #[serde(default)]
+    references: ReferenceInput,
+    /// Format of `references`; detected when omitted.
+    format: Option<ReferenceFormat>,
+    /// Cite corpus references tagged with any of these keywords instead of
+    /// sending references explicitly.
+    #[serde(default)]
+    keywords: Vec<String>,
+    /// Sorting, grouping and author substitution for bibliography previews.
+    bibliography_options: Option<BibliographyOptions>,
+}
+
+impl PreviewRequest {
+    /// Returns the explicit references, converted to CSLN, or the corpus
+    /// references matching `keywords`.
+    fn resolve_references(&self, state: &AppState) -> (Vec<Reference>, Vec<ConversionWarning>) {
+        if !self.references.is_empty() || self.keywords.is_empty() {
+            return formats::convert(&self.references, self.format);
+        }
+        let references = ReferenceFilter::from_keywords(&self.keywords)
+            .matching_ids(&state.metadata)
+            .iter()
+            .filter_map(|id| state.references.get(id).cloned())
+            .collect();
+        (references, Vec::new())
+    }
 }
 
 #[derive(Serialize)]
 struct PreviewResponse {
     result: String,
+    /// Problems converting the request's references, if any.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<ConversionWarning>,
 }
 
-async fn preview_citation(Json(payload): Json<PreviewRequest>) -> Json<PreviewResponse> {
+async fn preview_citation(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<PreviewRequest>
+) -> Json<PreviewResponse> {
     println!("Handling preview_citation request");
-    // 1. Convert Vec<Reference> to Bibliography (IndexMap)
-    let bib: Bibliography = payload.references
+    // 1. Convert the references to a Bibliography (IndexMap)
+    let (references, warnings) = payload.resolve_references(&state);
+    let bib: Bibliography = references
         .into_iter()
         .map(|r| (r.id().clone().unwrap_or_default(), r))
         .collect();
@@ -131,21 +238,53 @@ async fn preview_citation(Json(payload): Json<PreviewRequest>) -> Json<PreviewRe
         ..Default::default()
     };
 
-    // 5. Render
+    // 5. Render, escaped since clients insert the result as HTML
     let result = match processor.process_citation(&citation) {
-        Ok(res) => res,
+        Ok(res) => html::escape(&res),
         Err(e) => {
             println!("preview_citation error: {}", e);
-            format!("Error: {}", e)
+            html::escape(&format!("Error: {}", e))
         },
     };
 
-    Json(PreviewResponse { result })
+    Json(PreviewResponse { result, warnings })
 }
 
-async fn preview_bibliography(Json(payload): Json<PreviewRequest>) -> Json<PreviewResponse> {
+async fn preview_bibliography(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<PreviewRequest>
+) -> Json<PreviewResponse> {
     println!("Handling preview_bibliography request");
-    let bib: Bibliography = payload.references
+    let (references, warnings) = payload.resolve_references(&state);
+
+    if let Some(options) = &payload.bibliography_options {
+        let metadata: HashMap<String, ReferenceMeta> = references.iter()
+            .filter_map(|r| {
+                let id = r.id().clone().unwrap_or_default();
+                let value = serde_json::to_value(r).ok()?;
+                Some((id.clone(), ReferenceMeta::from_value(&id, &value)))
+            })
+            .collect();
+        let cite_ids: Vec<String> = references.iter().map(|r| r.id().clone().unwrap_or_default()).collect();
+        let by_id: HashMap<String, Reference> = cite_ids.iter().cloned().zip(references).collect();
+
+        let entries = bibliography::render_entries(&payload.style, &cite_ids, &by_id, &metadata);
+        let groups = bibliography::arrange(entries, &metadata, options);
+        let result = groups.iter()
+            .map(|group| {
+                let mut lines = Vec::new();
+                if let Some(heading) = &group.heading {
+                    lines.push(html::escape(heading));
+                }
+                lines.extend(group.entries.iter().map(|entry| html::escape(&entry.text)));
+                lines.join("\n")
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        return Json(PreviewResponse { result, warnings });
+    }
+
+    let bib: Bibliography = references
         .into_iter()
         .map(|r| (r.id().clone().unwrap_or_default(), r))
         .collect();
@@ -157,90 +296,213 @@ async fn preview_bibliography(Json(payload): Json<PreviewRequest>) -> Json<Previ
     let result = match output.bibliography.is_empty() {
         true => String::new(),
         false => output.bibliography.iter()
-            .map(|entry| csln_processor::citation_to_string(entry, None, None, None, None))
+            .map(|entry| html::escape(&csln_processor::citation_to_string(entry, None, None, None, None)))
             .collect::<Vec<String>>()
             .join("\n")
     };
 
-    Json(PreviewResponse { result })
+    Json(PreviewResponse { result, warnings })
 }
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 struct PreviewSet {
     in_text: Option<String>,
-    note: Option<String>,
+    notes: Option<Vec<NotePreview>>,
     bibliography: Option<String>,
+    fragments: Vec<PreviewFragment>,
+}
+
+/// Query parameters shared by the preview endpoints.
+#[derive(Default, Deserialize)]
+struct PreviewParams {
+    /// Comma-separated reference ids to cite instead of the field defaults,
+    /// e.g. `?refs=austen_pride,who_report`.
+    refs: Option<String>,
+    /// Comma-separated corpus keywords, e.g. `?keywords=law`. Ignored when
+    /// `refs` is given.
+    keywords: Option<String>,
+    /// BCP 47 locale for note terms such as "ibid.", e.g. `?locale=de-DE`.
+    locale: Option<String>,
+}
+
+impl PreviewParams {
+    fn pinned(&self, state: &AppState) -> Option<Vec<String>> {
+        if let Some(refs) = &self.refs {
+            return Some(corpus::split_list(refs));
+        }
+        self.keywords.as_ref().map(|keywords| {
+            let filter = ReferenceFilter::from_keywords(&corpus::split_list(keywords));
+            filter.matching_ids(&state.metadata)
+                .into_iter()
+                .take(corpus::PREVIEW_LIMIT)
+                .collect()
+        })
+    }
+
+    fn locale(&self) -> String {
+        self.locale.clone().unwrap_or_else(|| notes::DEFAULT_LOCALE.to_string())
+    }
 }
 
 async fn preview_set_handler(
     State(state): State<Arc<AppState>>,
+    Query(params): Query<PreviewParams>,
     Json(intent): Json<StyleIntent>
 ) -> Json<PreviewSet> {
-    Json(generate_preview_set(&intent, &state.references))
+    let pinned = params.pinned(&state);
+    let set = spawn_preview_render(state, intent, pinned, params.locale()).await.ok().flatten();
+    Json(set.unwrap_or_default())
 }
 
-/// Helper to generate preview HTML for a given intent and references
-fn generate_preview_set(intent: &StyleIntent, references: &HashMap<String, Reference>) -> PreviewSet {
-    let mut set = PreviewSet::default();
+/// Renders a preview set on the blocking thread pool, giving up after
+/// `RENDER_TIMEOUT`. The timer starts as soon as the task is spawned, so
+/// several renders can be spawned first and awaited in any order.
+///
+/// Blocking work can't be cancelled: a render that times out keeps running
+/// to completion and only its result is discarded. Each render holds one of
+/// `AppState::render_slots` until it actually finishes, so slow styles can't
+/// pile up unbounded work on the pool; renders still queued for a slot when
+/// the timeout fires never start.
+fn spawn_preview_render(
+    state: Arc<AppState>,
+    intent: StyleIntent,
+    pinned: Option<Vec<String>>,
+    locale: String,
+) -> tokio::task::JoinHandle<Option<PreviewSet>> {
+    tokio::spawn(async move {
+        let render = async move {
+            let permit = state.render_slots.clone().acquire_owned().await
+                .expect("Render semaphore is never closed");
+            tokio::task::spawn_blocking(move || {
+                let set = generate_preview_set(&intent, &state, pinned.as_deref(), &locale);
+                drop(permit);
+                set
+            }).await
+        };
+        match tokio::time::timeout(RENDER_TIMEOUT, render).await {
+            Ok(Ok(set)) => Some(set),
+            Ok(Err(e)) => {
+                println!("Preview render failed: {}", e);
+                None
+            },
+            Err(_) => {
+                println!("Preview render timed out after {:?}", RENDER_TIMEOUT);
+                None
+            }
+        }
+    })
+}
+
+async fn cache_stats(State(state): State<Arc<AppState>>) -> Json<cache::CacheStats> {
+    Json(state.preview_cache.stats())
+}
+
+/// Helper to generate preview HTML for a given intent, reusing cached
+/// renders of identical style and reference combinations
+fn generate_preview_set(
+    intent: &StyleIntent,
+    state: &AppState,
+    pinned: Option<&[String]>,
+    locale: &str,
+) -> PreviewSet {
     // Generate real preview using the processor
     let style = intent.to_style();
     println!("Generated style: {:?}", style);
     
-    // Pick a few diverse references to cite for the preview
-    // Prioritize specific references that show off style features
-    let candidates = ["vaswani_attention", "foucault_discipline", "brown_v_board"];
-    let mut cite_ids = Vec::new();
-    for id in candidates {
-        if references.contains_key(id) {
-            cite_ids.push(id.to_string());
-        }
-    }
-    
-    // Fallback to random ones if none found
-    if cite_ids.is_empty() {
-        cite_ids = references.keys().take(3).cloned().collect();
-    }
+    // Pick references suited to the intent's field, unless the caller pinned some
+    let cite_ids = corpus::select_preview_ids(intent.field.as_deref(), &state.references, pinned);
 
-    if cite_ids.is_empty() {
-        return set;
-    }
-
-    if let Some(class) = &intent.class {
-        // Create a small bibliography containing only the cited references
-        let bib: Bibliography = references.iter()
-            .filter(|(k, _)| cite_ids.contains(k))
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect();
+    let class = match &intent.class {
+        Some(class) if !cite_ids.is_empty() => class.clone(),
+        _ => return PreviewSet::default(),
+    };
+    let has_bibliography = intent.has_bibliography.unwrap_or(false);
+    let bib_options = BibliographyOptions::from_intent(intent);
+
+    let context = format!("{:?}|{}|{:?}|{}", class, has_bibliography, bib_options, locale);
+    let key = cache::render_key(&style, &cite_ids, &context);
+    state.preview_cache.get_or_insert_with(key, || {
+        render_preview_set(style, &class, has_bibliography.then_some(&bib_options), cite_ids, locale, state)
+    })
+}
 
-        let processor = Processor::new(style, bib);
-        let citation = Citation {
-            id: Some("preview-1".to_string()),
-            items: cite_ids.into_iter().map(|id| CitationItem { id, ..Default::default() }).collect(),
-            ..Default::default()
-        };
+fn render_preview_set(
+    style: Style,
+    class: &intent_engine::CitationClass,
+    bib_options: Option<&BibliographyOptions>,
+    cite_ids: Vec<String>,
+    locale: &str,
+    state: &AppState,
+) -> PreviewSet {
+    let mut set = PreviewSet::default();
 
-        if let Ok(res) = processor.process_citation(&citation) {
-            if !res.trim().is_empty() {
-                match class {
-                    intent_engine::CitationClass::AuthorDate => set.in_text = Some(res),
-                    intent_engine::CitationClass::Footnote | intent_engine::CitationClass::Endnote => set.note = Some(res),
-                    intent_engine::CitationClass::Numeric => set.in_text = Some(res), 
-                }
-            }
+    if let Some(options) = bib_options {
+        let entries = bibliography::render_entries(&style, &cite_ids, &state.references, &state.metadata);
+        let groups = bibliography::arrange(entries, &state.metadata, options);
+        if !groups.is_empty() {
+            let mut trace = html::Trace::new(class.clone(), PreviewContext::Bibliography);
+            set.bibliography = Some(bibliography::to_html(&groups, &state.metadata, &mut trace));
+            set.fragments.extend(trace.fragments);
         }
+    }
+
+    // Create a small bibliography containing only the cited references,
+    // in citation order so output is stable between requests
+    let bib: Bibliography = cite_ids.iter()
+        .filter_map(|id| state.references.get(id).map(|r| (id.clone(), r.clone())))
+        .collect();
 
-        if intent.has_bibliography.unwrap_or(false) {
-            let bib_output = processor.process_references();
-            if !bib_output.bibliography.is_empty() {
-                let mut bib_html = String::new();
-                for entry in bib_output.bibliography {
-                    let bib_str = csln_processor::citation_to_string(&entry, None, None, None, None);
-                    bib_html.push_str(&format!("<div class='bib-entry'>{}</div>", bib_str));
+    let processor = Processor::new(style, bib);
+
+    match class {
+        intent_engine::CitationClass::Footnote | intent_engine::CitationClass::Endnote => {
+            let mut trace = html::Trace::new(class.clone(), PreviewContext::Note);
+            let terms = notes::NoteTerms::for_locale(locale);
+            let notes = notes::render_notes(&cite_ids, &state.metadata, terms, corpus::PREVIEW_LOCATOR, &mut trace, |id, locator| {
+                let citation = Citation {
+                    id: Some(format!("note-{}", id)),
+                    items: vec![CitationItem {
+                        id: id.to_string(),
+                        locator: locator.map(str::to_string),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                };
+                processor.process_citation(&citation).map_err(|e| e.to_string())
+            });
+            // Keep fragments in page order, ahead of the bibliography's
+            set.fragments.splice(0..0, trace.fragments);
+            if !notes.is_empty() {
+                set.notes = Some(notes);
+            }
+        },
+        intent_engine::CitationClass::AuthorDate | intent_engine::CitationClass::Numeric => {
+            let metas: Vec<&ReferenceMeta> = cite_ids.iter()
+                .filter_map(|id| state.metadata.get(id))
+                .collect();
+            // Cite a page for the first reference so locator formatting shows
+            let citation = Citation {
+                id: Some("preview-1".to_string()),
+                items: cite_ids.into_iter()
+                    .enumerate()
+                    .map(|(i, id)| CitationItem {
+                        id,
+                        locator: (i == 0).then(|| corpus::PREVIEW_LOCATOR.to_string()),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+            if let Ok(res) = processor.process_citation(&citation) {
+                if !res.trim().is_empty() {
+                    let mut trace = html::Trace::new(class.clone(), PreviewContext::Citation);
+                    set.in_text = Some(html::citation(&res, &metas, Some(corpus::PREVIEW_LOCATOR), &mut trace));
+                    set.fragments.splice(0..0, trace.fragments);
                 }
-                set.bibliography = Some(bib_html);
             }
-        }
+        },
     }
+
     set
 }
 
@@ -252,45 +514,74 @@ fn generate_preview_set(intent: &StyleIntent, references: &HashMap<String, Refer
 /// 3. What are the preview options for that question?
 async fn decide_handler(
     State(state): State<Arc<AppState>>,
+    Query(params): Query<PreviewParams>,
     Json(intent): Json<StyleIntent>
-) -> Json<DecisionPackage> {
+) -> Result<Json<DecisionPackage>, (axum::http::StatusCode, String)> {
     println!("Handling decide request: {:?}", intent);
+    let pinned = params.pinned(&state);
+    let locale = params.locale();
     // Call the engine to determine the next decision based on current intent
     let mut package = intent.decide();
 
-    // 1. Generate live preview for the CURRENT intent
-    let current_previews = generate_preview_set(&intent, &state.references);
-    package.in_text_preview = current_previews.in_text;
-    package.note_preview = current_previews.note;
-    package.bibliography_preview = current_previews.bibliography;
-
-    // 2. Generate live previews for EACH choice
-    for preview in &mut package.previews {
-        match serde_json::to_value(&intent) {
-            Ok(mut intent_val) => {
-                if let Some(obj) = intent_val.as_object_mut() {
-                    if let Some(choice_obj) = preview.choice_value.as_object() {
-                        for (k, v) in choice_obj {
-                            obj.insert(k.clone(), v.clone());
-                        }
-                    }
-                }
+    // 1. Start the live preview for the CURRENT intent
+    let current = spawn_preview_render(state.clone(), intent.clone(), pinned.clone(), locale.clone());
+
+    // 2. Start live previews for EACH choice, all rendering concurrently
+    let mut choices = Vec::new();
+    for preview in &package.previews {
+        // A choice that can't be applied means the submitted intent is
+        // inconsistent with what the engine offers, so name the choice
+        let choice_intent = preview.choice_value.apply(&intent).map_err(|e| {
+            println!("Error applying choice '{}': {}", preview.label, e);
+            (axum::http::StatusCode::UNPROCESSABLE_ENTITY, format!("Choice '{}' could not be applied: {}", preview.label, e))
+        })?;
+        choices.push(spawn_preview_render(state.clone(), choice_intent, pinned.clone(), locale.clone()));
+    }
 
-                if let Ok(temp_intent) = serde_json::from_value::<StyleIntent>(intent_val) {
-                    let p_set = generate_preview_set(&temp_intent, &state.references);
-                    // For choices, we still want a single HTML string for the small card preview
-                    let mut html = String::new();
-                    if let Some(it) = p_set.in_text { html.push_str(&format!("<div class='cit'>{}</div>", it)); }
-                    if let Some(nt) = p_set.note { html.push_str(&format!("<div class='cit'>{}</div>", nt)); }
-                    if let Some(bb) = p_set.bibliography { html.push_str(&format!("<div class='bib'>{}</div>", bb)); }
-                    preview.html = html;
+    if let Some(current_previews) = current.await.ok().flatten() {
+        package.in_text_preview = current_previews.in_text;
+        package.note_preview = current_previews.notes;
+        package.bibliography_preview = current_previews.bibliography;
+        package.fragments = current_previews.fragments;
+    }
+
+    for (preview, handle) in package.previews.iter_mut().zip(choices) {
+        if let Some(p_set) = handle.await.ok().flatten() {
+            // For choices, we still want a single HTML string for the small card preview
+            let mut html = String::new();
+            // Fragments are already escaped and annotated by the renderer
+            if let Some(it) = p_set.in_text { html.push_str(&format!("<p class=\"csl-preview-citation\">{}</p>", it)); }
+            if let Some(notes) = p_set.notes {
+                html.push_str("<ol class=\"csl-notes\">");
+                for note in notes {
+                    html.push_str(&format!("<li value=\"{}\" class=\"csl-note\">{}</li>", note.number, note.text));
                 }
-            },
-            Err(e) => println!("Error serializing intent: {}", e),
+                html.push_str("</ol>");
+            }
+            if let Some(bb) = p_set.bibliography { html.push_str(&bb); }
+            preview.html = html;
         }
     }
 
-    Json(package)
+    Ok(Json(package))
+}
+
+#[derive(Deserialize)]
+struct PatchIntentRequest {
+    intent: StyleIntent,
+    patch: StyleIntentPatch,
+}
+
+/// Handler for the `/api/v1/intent` endpoint.
+///
+/// Applies a `StyleIntentPatch` to the given intent and returns the result,
+/// or a 422 explaining why the patch was rejected.
+async fn patch_intent_handler(
+    Json(payload): Json<PatchIntentRequest>
+) -> Result<Json<StyleIntent>, (axum::http::StatusCode, String)> {
+    payload.patch.apply(&payload.intent)
+        .map(Json)
+        .map_err(|e| (axum::http::StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))
 }
 
 /// Handler for the `/api/v1/generate` endpoint.
@@ -310,4 +601,12 @@ async fn generate_handler(Json(intent): Json<StyleIntent>) -> (axum::http::Heade
     );
 
     (headers, csln)
+}
+
+/// Handler for the `/api/v1/export/csl` endpoint.
+///
+/// Receives the final `StyleIntent` and returns a CSL 1.0 style for
+/// Zotero, Mendeley and citeproc, with the features lost in translation.
+async fn export_csl_handler(Json(intent): Json<StyleIntent>) -> Json<intent_engine::csl::CslExport> {
+    Json(intent_engine::csl::export(&intent.to_style(), Some(&intent)))
 }
\ No newline at end of file
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use intent_engine::{NotePosition, NotePreview};
+
+use crate::corpus::ReferenceMeta;
+use crate::html;
+
+/// Locale used for note terms when the request doesn't name one.
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+/// Most words of a title kept in a note's short form.
+const SHORT_TITLE_WORDS: usize = 4;
+
+/// Localised terms used when shortening repeated notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteTerms {
+    pub ibid: &'static str,
+    /// Joins the last two names in a short-form author list.
+    pub and: &'static str,
+}
+
+impl NoteTerms {
+    /// Looks up terms by BCP 47 tag, matching on the primary language and
+    /// falling back to English.
+    pub fn for_locale(locale: &str) -> Self {
+        let language = locale.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+        let (ibid, and) = match language.as_str() {
+            "de" => ("Ebd.", "und"),
+            "es" => ("Ibid.", "y"),
+            "fr" => ("Ibid.", "et"),
+            "it" => ("Ivi", "e"),
+            "nl" => ("Ibid.", "en"),
+            "pt" => ("Ibidem", "e"),
+            _ => ("Ibid.", "and"),
+        };
+        NoteTerms { ibid, and }
+    }
+}
+
+/// Builds the short form used for subsequent notes, e.g. "Austen and
+/// Bennet, Pride and Prejudice": family names, then the title up to any
+/// subtitle, cut to its first few words.
+///
+/// Returns `None` when the reference has no title to shorten.
+pub fn short_form(meta: &ReferenceMeta, terms: NoteTerms) -> Option<String> {
+    let title = meta.title.as_deref()?;
+    let main = title.split([':', '?', '!']).next().unwrap_or(title).trim();
+    let short_title = main.split_whitespace()
+        .take(SHORT_TITLE_WORDS)
+        .collect::<Vec<_>>()
+        .join(" ");
+    if short_title.is_empty() {
+        return None;
+    }
+
+    let names = match meta.authors.as_slice() {
+        [] => None,
+        [one] => Some(one.clone()),
+        [first, second] => Some(format!("{} {} {}", first, terms.and, second)),
+        [first, second, third] => Some(format!("{}, {}, {} {}", first, second, terms.and, third)),
+        [first, ..] => Some(format!("{} et al.", first)),
+    };
+    Some(match names {
+        Some(names) => format!("{}, {}", names, short_title),
+        None => short_title,
+    })
+}
+
+/// Renders a run of notes citing each reference in turn, then citing the
+/// first one again twice, so the preview shows first, subsequent and ibid
+/// forms in page order.
+///
+/// `render_full` renders a reference's first (full) note, citing the
+/// locator it is given. Only the first note cites `locator`, so the preview
+/// shows how the style presents one. References `render_full` can't render
+/// are left out before the run is numbered, so the notes that remain are
+/// numbered without gaps and keep correct positions.
+pub fn render_notes(
+    cite_ids: &[String],
+    metadata: &HashMap<String, ReferenceMeta>,
+    terms: NoteTerms,
+    locator: &str,
+    trace: &mut html::Trace,
+    render_full: impl Fn(&str, Option<&str>) -> Result<String, String>,
+) -> Vec<NotePreview> {
+    let mut full: HashMap<&str, String> = HashMap::new();
+    let mut cited: Vec<String> = Vec::new();
+    for id in cite_ids {
+        let note_locator = cited.is_empty().then_some(locator);
+        match render_full(id, note_locator) {
+            Ok(text) => {
+                full.insert(id.as_str(), text);
+                cited.push(id.clone());
+            },
+            Err(e) => println!("Error rendering note for {}: {}", id, e),
+        }
+    }
+
+    let mut sequence = cited.clone();
+    if let Some(first) = cited.first() {
+        if cited.len() > 1 {
+            sequence.push(first.clone());
+        }
+        sequence.push(first.clone());
+    }
+
+    let positions = NotePosition::sequence(&sequence);
+    sequence.into_iter()
+        .zip(positions)
+        .enumerate()
+        .map(|(i, (id, position))| {
+            let meta = metadata.get(&id);
+            let text = match position {
+                NotePosition::Ibid => terms.ibid.to_string(),
+                NotePosition::Subsequent => meta
+                    .and_then(|meta| short_form(meta, terms))
+                    .unwrap_or_else(|| full[id.as_str()].clone()),
+                NotePosition::First => full[id.as_str()].clone(),
+            };
+            let note_locator = (i == 0).then_some(locator);
+            let text = match meta {
+                Some(meta) if position != NotePosition::Ibid => html::annotate(&text, &[meta], note_locator, trace),
+                _ => html::escape(&text),
+            };
+            NotePreview { number: (i + 1) as u32, reference_id: id, position, text }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intent_engine::{CitationClass, PreviewContext};
+
+    fn meta(id: &str, authors: &[&str], title: &str) -> ReferenceMeta {
+        ReferenceMeta {
+            id: id.to_string(),
+            ref_type: "book".to_string(),
+            title: Some(title.to_string()),
+            authors: authors.iter().map(|a| a.to_string()).collect(),
+            year: Some("1813".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn corpus() -> HashMap<String, ReferenceMeta> {
+        [
+            meta("austen", &["Austen"], "Pride and Prejudice: A Novel in Three Volumes"),
+            meta("who", &["Smith", "Jones"], "World Health Report"),
+        ]
+        .into_iter()
+        .map(|m| (m.id.clone(), m))
+        .collect()
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn render(terms: NoteTerms, cite_ids: &[String]) -> Vec<NotePreview> {
+        let mut trace = html::Trace::new(CitationClass::Footnote, PreviewContext::Note);
+        render_notes(cite_ids, &corpus(), terms, "34", &mut trace, |id, locator| match (id, locator) {
+            ("broken", _) => Err("no template".to_string()),
+            (id, Some(locator)) => Ok(format!("FULL {}, {}", id, locator)),
+            (id, None) => Ok(format!("FULL {}", id)),
+        })
+    }
+
+    /// Note text with the annotation markup removed.
+    fn plain(note: &NotePreview) -> String {
+        crate::corpus::strip_tags(&note.text)
+    }
+
+    #[test]
+    fn renders_first_subsequent_and_ibid_forms() {
+        let notes = render(NoteTerms::for_locale("en-US"), &ids(&["austen", "who"]));
+        let texts: Vec<String> = notes.iter().map(plain).collect();
+        assert_eq!(texts, [
+            "FULL austen, 34",
+            "FULL who",
+            "Austen, Pride and Prejudice",
+            "Ibid.",
+        ]);
+        let positions: Vec<NotePosition> = notes.iter().map(|n| n.position).collect();
+        assert_eq!(positions, [
+            NotePosition::First,
+            NotePosition::First,
+            NotePosition::Subsequent,
+            NotePosition::Ibid,
+        ]);
+    }
+
+    #[test]
+    fn note_fragments_point_at_the_fields_that_control_them() {
+        let mut trace = html::Trace::new(CitationClass::Footnote, PreviewContext::Note);
+        render_notes(&ids(&["austen"]), &corpus(), NoteTerms::for_locale("en"), "34", &mut trace, |id, locator| {
+            Ok(format!("Austen, {}, {}", id, locator.unwrap_or_default()))
+        });
+        let fields: Vec<(&str, Option<&str>)> = trace.fragments.iter()
+            .map(|f| (f.component.as_str(), f.intent_field.as_deref()))
+            .collect();
+        assert!(fields.contains(&("contributor:author", Some("author_format"))));
+        assert!(fields.contains(&("locator", Some("citation_preset"))));
+        assert!(fields.iter().all(|(_, field)| *field != Some("class")));
+    }
+
+    #[test]
+    fn localises_ibid() {
+        let notes = render(NoteTerms::for_locale("de-DE"), &ids(&["austen", "who"]));
+        assert_eq!(plain(&notes[3]), "Ebd.");
+    }
+
+    #[test]
+    fn failed_renders_leave_no_gaps() {
+        let notes = render(NoteTerms::for_locale("en-US"), &ids(&["broken", "austen", "who"]));
+        let numbers: Vec<u32> = notes.iter().map(|n| n.number).collect();
+        assert_eq!(numbers, [1, 2, 3, 4]);
+        assert_eq!(notes[0].reference_id, "austen");
+        // The locator moves to the first note that did render
+        assert_eq!(plain(&notes[0]), "FULL austen, 34");
+        assert_eq!(notes[2].position, NotePosition::Subsequent);
+    }
+
+    #[test]
+    fn single_reference_goes_straight_to_ibid() {
+        let notes = render(NoteTerms::for_locale("en-US"), &ids(&["austen"]));
+        let texts: Vec<String> = notes.iter().map(plain).collect();
+        assert_eq!(texts, ["FULL austen, 34", "Ibid."]);
+    }
+
+    #[test]
+    fn short_form_joins_names_with_the_localised_and() {
+        let who = &corpus()["who"];
+        assert_eq!(short_form(who, NoteTerms::for_locale("en")).unwrap(), "Smith and Jones, World Health Report");
+        assert_eq!(short_form(who, NoteTerms::for_locale("fr-FR")).unwrap(), "Smith et Jones, World Health Report");
+
+        let many = meta("many", &["A", "B", "C", "D"], "Collected Papers");
+        assert_eq!(short_form(&many, NoteTerms::for_locale("en")).unwrap(), "A et al., Collected Papers");
+    }
+
+    #[test]
+    fn short_form_needs_a_title() {
+        let mut untitled = meta("untitled", &["Austen"], "");
+        assert_eq!(short_form(&untitled, NoteTerms::for_locale("en")), None);
+        untitled.title = None;
+        assert_eq!(short_form(&untitled, NoteTerms::for_locale("en")), None);
+    }
+
+    #[test]
+    fn unknown_locales_fall_back_to_english() {
+        assert_eq!(NoteTerms::for_locale("xx-YY"), NoteTerms::for_locale("en-US"));
+        assert_eq!(NoteTerms::for_locale("").ibid, "Ibid.");
+    }
+}
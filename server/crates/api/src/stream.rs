@@ -0,0 +1,195 @@
+use axum::{
+    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, State},
+    response::Response,
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use csln_core::Style;
+use csln_processor::{Processor, Bibliography, Citation, CitationItem};
+use intent_engine::StyleIntent;
+
+use crate::{corpus, html, AppState};
+
+/// An edit pushed by the client. Each one supersedes any render still in flight.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamRequest {
+    Intent {
+        intent: StyleIntent,
+        #[serde(default)]
+        refs: Vec<String>,
+    },
+    Style {
+        style: Style,
+        #[serde(default)]
+        refs: Vec<String>,
+    },
+}
+
+/// A rendered fragment sent back to the client, tagged with the revision
+/// (edit number) it belongs to.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    Citation { revision: u64, index: usize, id: String, html: String },
+    BibliographyEntry { revision: u64, index: usize, html: String },
+    Done { revision: u64 },
+    Error { revision: u64, message: String },
+}
+
+impl StreamEvent {
+    fn revision(&self) -> u64 {
+        match self {
+            StreamEvent::Citation { revision, .. }
+            | StreamEvent::BibliographyEntry { revision, .. }
+            | StreamEvent::Done { revision }
+            | StreamEvent::Error { revision, .. } => *revision,
+        }
+    }
+}
+
+/// Handler for the `/api/v1/preview/stream` WebSocket endpoint.
+///
+/// The client pushes intent or style edits as JSON messages and receives
+/// citations and bibliography entries one at a time as they are rendered.
+/// A newer edit cancels whatever is left of the previous render.
+pub async fn preview_stream_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, state))
+}
+
+async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<StreamEvent>();
+    let latest = Arc::new(AtomicU64::new(0));
+
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => {
+                let text = match incoming {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        println!("Preview stream error: {}", e);
+                        break;
+                    }
+                };
+
+                let revision = latest.fetch_add(1, Ordering::SeqCst) + 1;
+                match serde_json::from_str::<StreamRequest>(&text) {
+                    Ok(request) => {
+                        let state = state.clone();
+                        let latest = latest.clone();
+                        let tx = tx.clone();
+                        // Renders share the preview render slots. Edits that
+                        // are superseded while waiting for a slot never start;
+                        // renders can't be interrupted mid-call, so stale ones
+                        // stop at the next fragment boundary instead
+                        tokio::spawn(async move {
+                            let permit = state.render_slots.clone().acquire_owned().await
+                                .expect("Render semaphore is never closed");
+                            if latest.load(Ordering::SeqCst) != revision {
+                                return;
+                            }
+                            tokio::task::spawn_blocking(move || {
+                                render_revision(&state, request, revision, &latest, &tx);
+                                drop(permit);
+                            });
+                        });
+                    },
+                    Err(e) => {
+                        let _ = tx.send(StreamEvent::Error { revision, message: e.to_string() });
+                    }
+                }
+            },
+            Some(event) = rx.recv() => {
+                // Drop anything queued by a render that has since been superseded
+                if event.revision() != latest.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let payload = match serde_json::to_string(&event) {
+                    Ok(payload) => payload,
+                    Err(e) => {
+                        println!("Error serializing stream event: {}", e);
+                        continue;
+                    }
+                };
+                if socket.send(Message::Text(payload)).await.is_err() {
+                    break;
+                }
+            },
+        }
+    }
+
+    // Let any in-flight render notice it is stale and stop
+    latest.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Renders one revision, checking before each fragment whether a newer
+/// edit has arrived.
+fn render_revision(
+    state: &AppState,
+    request: StreamRequest,
+    revision: u64,
+    latest: &AtomicU64,
+    tx: &mpsc::UnboundedSender<StreamEvent>,
+) {
+    let is_stale = || latest.load(Ordering::SeqCst) != revision;
+
+    // Intents say whether they want a bibliography, as the REST preview
+    // reads it; raw styles have one if they define it
+    let (style, field, has_bibliography, refs) = match request {
+        StreamRequest::Intent { intent, refs } => {
+            let has_bibliography = intent.has_bibliography.unwrap_or(false);
+            (intent.to_style(), intent.field, has_bibliography, refs)
+        },
+        StreamRequest::Style { style, refs } => {
+            let has_bibliography = style.bibliography.is_some();
+            (style, None, has_bibliography, refs)
+        },
+    };
+    let pinned = (!refs.is_empty()).then_some(refs.as_slice());
+    let cite_ids = corpus::select_preview_ids(field.as_deref(), &state.references, pinned);
+
+    let bib: Bibliography = cite_ids.iter()
+        .filter_map(|id| state.references.get(id).map(|r| (id.clone(), r.clone())))
+        .collect();
+    let processor = Processor::new(style, bib);
+
+    for (index, id) in cite_ids.iter().enumerate() {
+        if is_stale() {
+            return;
+        }
+        let citation = Citation {
+            id: Some(format!("stream-{}", index + 1)),
+            items: vec![CitationItem { id: id.clone(), ..Default::default() }],
+            ..Default::default()
+        };
+        let event = match processor.process_citation(&citation) {
+            Ok(text) => StreamEvent::Citation { revision, index, id: id.clone(), html: html::escape(&text) },
+            Err(e) => StreamEvent::Error { revision, message: e.to_string() },
+        };
+        if tx.send(event).is_err() {
+            return;
+        }
+    }
+
+    if has_bibliography && !is_stale() {
+        let output = processor.process_references();
+        for (index, entry) in output.bibliography.iter().enumerate() {
+            if is_stale() {
+                return;
+            }
+            let html = html::escape(&csln_processor::citation_to_string(entry, None, None, None, None));
+            if tx.send(StreamEvent::BibliographyEntry { revision, index, html }).is_err() {
+                return;
+            }
+        }
+    }
+
+    let _ = tx.send(StreamEvent::Done { revision });
+}
@@ -0,0 +1,278 @@
+use axum::{extract::State, http::StatusCode, Json};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use serde::Serialize;
+use csln_core::Style;
+use csln_processor::{Processor, Bibliography, Citation, CitationItem};
+use intent_engine::StyleIntent;
+
+use crate::corpus::{strip_tags, ReferenceMeta};
+use crate::html;
+use crate::AppState;
+
+/// Tokens that indicate a template rendered an unresolved value.
+const PLACEHOLDER_TOKENS: [&str; 5] = ["{{", "}}", "Some(", "undefined", "null"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StressWarning {
+    EmptyCitation,
+    EmptyBibliography,
+    MissingTitle,
+    MissingAuthor,
+    MissingDate,
+    RawPlaceholder { token: String },
+    RenderError { message: String },
+}
+
+#[derive(Serialize)]
+pub struct StressEntry {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub ref_type: String,
+    /// The rendered citation, HTML-escaped.
+    pub citation: String,
+    /// The rendered bibliography entry, HTML-escaped.
+    pub bibliography: Option<String>,
+    pub warnings: Vec<StressWarning>,
+}
+
+#[derive(Default, Serialize)]
+pub struct TypeCoverage {
+    pub total: usize,
+    pub clean: usize,
+    pub flagged: usize,
+}
+
+#[derive(Serialize)]
+pub struct StressReport {
+    pub total: usize,
+    pub flagged: usize,
+    pub coverage: BTreeMap<String, TypeCoverage>,
+    pub entries: Vec<StressEntry>,
+}
+
+/// Handler for the `/api/v1/stress-test` endpoint.
+///
+/// Renders every reference in the loaded corpus under the style generated
+/// from the given `StyleIntent` and reports entries that look broken.
+pub async fn stress_test_handler(
+    State(state): State<Arc<AppState>>,
+    Json(intent): Json<StyleIntent>
+) -> Result<Json<StressReport>, (StatusCode, String)> {
+    let style = intent.to_style();
+    // Rendering the whole corpus is CPU-bound, so keep it off the executor
+    // and count it against the render slots like any preview
+    let permit = state.render_slots.clone().acquire_owned().await
+        .expect("Render semaphore is never closed");
+    tokio::task::spawn_blocking(move || {
+        let report = run_stress_test(&style, &state);
+        drop(permit);
+        report
+    })
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Stress test failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("Stress test failed: {}", e))
+        })
+}
+
+/// Renders each reference in isolation and collects warnings per entry.
+pub fn run_stress_test(style: &Style, state: &AppState) -> StressReport {
+    let has_bibliography = style.bibliography.is_some();
+
+    let mut ids: Vec<&String> = state.references.keys().collect();
+    ids.sort();
+
+    let mut entries = Vec::new();
+    let mut coverage: BTreeMap<String, TypeCoverage> = BTreeMap::new();
+
+    for id in ids {
+        let reference = &state.references[id];
+        let meta = state.metadata.get(id).cloned().unwrap_or_else(|| ReferenceMeta {
+            id: id.clone(),
+            ref_type: "unknown".to_string(),
+            ..Default::default()
+        });
+
+        let bib: Bibliography = std::iter::once((id.clone(), reference.clone())).collect();
+        let processor = Processor::new(style.clone(), bib);
+
+        let mut warnings = Vec::new();
+
+        let citation = Citation {
+            id: Some(format!("stress-{}", id)),
+            items: vec![CitationItem { id: id.clone(), ..Default::default() }],
+            ..Default::default()
+        };
+        let citation_out = match processor.process_citation(&citation) {
+            Ok(res) => res,
+            Err(e) => {
+                warnings.push(StressWarning::RenderError { message: e.to_string() });
+                String::new()
+            }
+        };
+        if citation_out.trim().is_empty() {
+            warnings.push(StressWarning::EmptyCitation);
+        }
+
+        let bibliography_out = if has_bibliography {
+            let output = processor.process_references();
+            let rendered = output.bibliography.iter()
+                .map(|entry| csln_processor::citation_to_string(entry, None, None, None, None))
+                .collect::<Vec<String>>()
+                .join("\n");
+            if rendered.trim().is_empty() {
+                warnings.push(StressWarning::EmptyBibliography);
+            }
+            Some(rendered)
+        } else {
+            None
+        };
+
+        // Check the fullest rendering available: the bibliography entry if
+        // the style has one, otherwise the citation (e.g. notes-only styles).
+        let checked = strip_tags(bibliography_out.as_deref().unwrap_or(&citation_out));
+        warnings.extend(check_rendering(&checked, &meta));
+
+        let cov = coverage.entry(meta.ref_type.clone()).or_default();
+        cov.total += 1;
+        if warnings.is_empty() {
+            cov.clean += 1;
+        } else {
+            cov.flagged += 1;
+        }
+
+        entries.push(StressEntry {
+            id: id.clone(),
+            ref_type: meta.ref_type,
+            // Checked as plain text above, escaped for display
+            citation: html::escape(&citation_out),
+            bibliography: bibliography_out.as_deref().map(html::escape),
+            warnings,
+        });
+    }
+
+    StressReport {
+        total: entries.len(),
+        flagged: entries.iter().filter(|e| !e.warnings.is_empty()).count(),
+        coverage,
+        entries,
+    }
+}
+
+/// Compares rendered plain text against the reference's metadata.
+fn check_rendering(text: &str, meta: &ReferenceMeta) -> Vec<StressWarning> {
+    let mut warnings = Vec::new();
+    if text.trim().is_empty() {
+        // Already reported as empty output
+        return warnings;
+    }
+    let lower = text.to_lowercase();
+
+    if let Some(title) = &meta.title {
+        // Titles may be re-cased or re-punctuated, so only match the opening words
+        let opening: String = title.to_lowercase()
+            .split_whitespace()
+            .take(3)
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !opening.is_empty() && !lower.contains(&opening) {
+            warnings.push(StressWarning::MissingTitle);
+        }
+    }
+
+    if !meta.authors.is_empty() && !meta.authors.iter().any(|a| lower.contains(&a.to_lowercase())) {
+        warnings.push(StressWarning::MissingAuthor);
+    }
+
+    if let Some(year) = &meta.year {
+        if !text.contains(year.as_str()) {
+            warnings.push(StressWarning::MissingDate);
+        }
+    }
+
+    for token in PLACEHOLDER_TOKENS {
+        // Word-like tokens must match whole words ("null" but not "annulled")
+        let found = if token.chars().all(char::is_alphabetic) {
+            text.split(|c: char| !c.is_alphanumeric()).any(|w| w == token)
+        } else {
+            text.contains(token)
+        };
+        if found {
+            warnings.push(StressWarning::RawPlaceholder { token: token.to_string() });
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta() -> ReferenceMeta {
+        ReferenceMeta {
+            id: "austen_pride".to_string(),
+            ref_type: "book".to_string(),
+            title: Some("Pride and Prejudice Revisited".to_string()),
+            authors: vec!["Austen".to_string()],
+            year: Some("1813".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn complete_rendering_has_no_warnings() {
+        let text = "Austen, J. (1813). Pride and prejudice revisited. London.";
+        assert!(check_rendering(text, &meta()).is_empty());
+    }
+
+    #[test]
+    fn empty_rendering_is_left_to_the_caller() {
+        assert!(check_rendering("  ", &meta()).is_empty());
+    }
+
+    #[test]
+    fn reports_missing_title_author_and_date() {
+        let warnings = check_rendering("Some other work.", &meta());
+        assert!(matches!(warnings[..], [
+            StressWarning::MissingTitle,
+            StressWarning::MissingAuthor,
+            StressWarning::MissingDate,
+        ]));
+    }
+
+    #[test]
+    fn matches_only_the_opening_words_of_the_title() {
+        let text = "Austen (1813) Pride and Prejudice.";
+        assert!(check_rendering(text, &meta()).is_empty());
+    }
+
+    #[test]
+    fn any_author_is_enough() {
+        let mut meta = meta();
+        meta.authors.push("Bennet".to_string());
+        let text = "Bennet 1813, Pride and prejudice";
+        assert!(check_rendering(text, &meta).is_empty());
+    }
+
+    #[test]
+    fn reports_placeholder_tokens() {
+        let text = "Austen 1813, Pride and prejudice, {{publisher}}, null";
+        let tokens: Vec<String> = check_rendering(text, &meta()).into_iter()
+            .filter_map(|w| match w {
+                StressWarning::RawPlaceholder { token } => Some(token),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tokens, ["{{", "}}", "null"]);
+    }
+
+    #[test]
+    fn word_placeholders_match_whole_words_only() {
+        let text = "Austen 1813, Pride and prejudice. The contract was annulled.";
+        assert!(check_rendering(text, &meta()).is_empty());
+    }
+}
@@ -0,0 +1,753 @@
+//! Conversion between CSL 1.0 XML styles and CSLN.
+
+use std::collections::HashMap;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use serde::Serialize;
+use specta::Type;
+
+use crate::{BibliographySort, CitationClass, EtAlConfig, NameForm, NameOptions, StyleIntent};
+
+/// The result of importing a CSL 1.0 style.
+#[derive(Debug, Clone)]
+pub struct CslImport {
+    pub style: csln_core::Style,
+    /// The wizard choices that best reproduce the CSL style.
+    pub intent: StyleIntent,
+    /// Features of the CSL style that the import could not carry over.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CslError {
+    /// The input is not well-formed XML.
+    Xml(String),
+    /// The XML is not a CSL style.
+    NotCsl(String),
+}
+
+impl std::fmt::Display for CslError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CslError::Xml(message) => write!(f, "invalid XML: {}", message),
+            CslError::NotCsl(message) => write!(f, "not a CSL style: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CslError {}
+
+/// A parsed XML element. CSL styles are small, so a tree is simpler to
+/// query than the event stream.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn from_start(start: &BytesStart) -> Result<Self, CslError> {
+        let mut element = Element {
+            name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+            ..Default::default()
+        };
+        for attr in start.attributes() {
+            let attr = attr.map_err(|e| CslError::Xml(e.to_string()))?;
+            let value = attr.unescape_value().map_err(|e| CslError::Xml(e.to_string()))?;
+            element.attrs.push((String::from_utf8_lossy(attr.key.local_name().as_ref()).into_owned(), value.into_owned()));
+        }
+        Ok(element)
+    }
+
+    fn attr(&self, key: &str) -> Option<&str> {
+        self.attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    fn count(&self, name: &str) -> usize {
+        self.children.iter().map(|c| c.count(name)).sum::<usize>() + usize::from(self.name == name)
+    }
+}
+
+fn parse(xml: &str) -> Result<Element, CslError> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut stack: Vec<Element> = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => stack.push(Element::from_start(&start)?),
+            Ok(Event::Empty(start)) => {
+                let element = Element::from_start(&start)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            },
+            Ok(Event::Text(text)) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&text.unescape().map_err(|e| CslError::Xml(e.to_string()))?);
+                }
+            },
+            Ok(Event::End(_)) => {
+                let element = stack.pop().ok_or_else(|| CslError::Xml("unexpected closing tag".to_string()))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            },
+            Ok(Event::Eof) => return Err(CslError::Xml("unexpected end of input".to_string())),
+            Err(e) => return Err(CslError::Xml(e.to_string())),
+            _ => {},
+        }
+    }
+}
+
+/// What encloses a node in a layout, once macros are expanded.
+#[derive(Clone, Copy, Default)]
+struct Context<'a> {
+    /// Some enclosing node opens a parenthesis.
+    parenthesized: bool,
+    /// Prefix of the nearest enclosing group.
+    group_prefix: Option<&'a str>,
+}
+
+/// Most nodes a single walk visits; macros that fan out into each other can
+/// otherwise expand exponentially.
+const MAX_WALK_NODES: usize = 100_000;
+
+/// Bookkeeping for one walk through a layout.
+#[derive(Default)]
+struct Expansion<'a> {
+    /// Macros currently being expanded, innermost last.
+    macros: Vec<&'a str>,
+    /// Nodes visited so far.
+    visited: usize,
+}
+
+/// Calls `visit` on every node under `node`, expanding `text macro="…"`
+/// calls in place. A macro that calls itself, directly or not, is expanded
+/// only once per branch.
+fn walk<'a>(
+    node: &'a Element,
+    macros: &HashMap<&str, &'a Element>,
+    context: Context<'a>,
+    depth: usize,
+    expansion: &mut Expansion<'a>,
+    visit: &mut dyn FnMut(&'a Element, Context<'a>),
+) {
+    // Guards against deeply nested layouts as well as long macro chains
+    if depth > 32 || expansion.visited >= MAX_WALK_NODES {
+        return;
+    }
+    expansion.visited += 1;
+    let context = Context {
+        parenthesized: context.parenthesized || node.attr("prefix").is_some_and(|p| p.contains('(')),
+        group_prefix: if node.name == "group" { node.attr("prefix") } else { context.group_prefix },
+    };
+    visit(node, context);
+    if let Some(name) = node.attr("macro").filter(|name| !expansion.macros.contains(name)) {
+        if let Some(body) = macros.get(name) {
+            expansion.macros.push(name);
+            for child in &body.children {
+                walk(child, macros, context, depth + 1, expansion, visit);
+            }
+            expansion.macros.pop();
+        }
+    }
+    for child in &node.children {
+        walk(child, macros, context, depth + 1, expansion, visit);
+    }
+}
+
+/// CSL `<category field="…">` values grouped into the wizard's fields.
+fn wizard_field(csl_field: &str) -> Option<&'static str> {
+    Some(match csl_field {
+        "humanities" | "history" | "philosophy" | "literature" | "theology" | "linguistics" | "communications" => "humanities",
+        "social_science" | "anthropology" | "sociology" | "political_science" | "psychology" | "geography" => "social_science",
+        "science" | "biology" | "botany" | "chemistry" | "physics" | "medicine" | "engineering"
+        | "math" | "astronomy" | "geology" | "zoology" => "sciences",
+        _ => return None,
+    })
+}
+
+fn et_al(node: &Element) -> Option<(u8, u8)> {
+    let min = node.attr("et-al-min")?.parse().ok()?;
+    let use_first = node.attr("et-al-use-first")?.parse().ok()?;
+    Some((min, use_first))
+}
+
+/// Imports a CSL 1.0 style.
+///
+/// CSL layouts are not translated node by node: the wizard choices are
+/// inferred from the CSL and the CSLN style is rebuilt from them, keeping
+/// the CSL title and id. Anything that doesn't survive is listed in the
+/// warnings.
+pub fn import(xml: &str) -> Result<CslImport, CslError> {
+    let root = parse(xml)?;
+    if root.name != "style" {
+        return Err(CslError::NotCsl(format!("root element is <{}>", root.name)));
+    }
+    let citation = root.child("citation").ok_or_else(|| CslError::NotCsl("no <citation> element".to_string()))?;
+    let bibliography = root.child("bibliography");
+    let macros: HashMap<&str, &Element> = root.children_named("macro")
+        .filter_map(|m| Some((m.attr("name")?, m)))
+        .collect();
+
+    let mut intent = StyleIntent::default();
+    let mut warnings = Vec::new();
+    let info = root.child("info");
+
+    intent.field = info.into_iter()
+        .flat_map(|info| info.children_named("category"))
+        .filter_map(|c| c.attr("field").and_then(wizard_field))
+        .next()
+        .map(str::to_string);
+
+    // Class
+    let mut numbered = false;
+    let mut name_form = NameForm::Long;
+    let mut locator_prefix = None;
+    walk(citation, &macros, Context::default(), 0, &mut Expansion::default(), &mut |node, context| {
+        match (node.name.as_str(), node.attr("variable")) {
+            (_, Some("citation-number")) => numbered = true,
+            ("text" | "number" | "label", Some("locator")) => {
+                locator_prefix = locator_prefix.or(node.attr("prefix").or(context.group_prefix));
+            },
+            ("name", _) if node.attr("form") == Some("short") => name_form = NameForm::Short,
+            _ => {},
+        }
+    });
+    intent.class = Some(match root.attr("class") {
+        Some("note") => {
+            warnings.push("CSL does not distinguish footnotes from endnotes; imported as footnotes".to_string());
+            CitationClass::Footnote
+        },
+        _ if numbered => CitationClass::Numeric,
+        _ => CitationClass::AuthorDate,
+    });
+
+    // Names, with options inherited from <style> down to <citation> and <name>
+    let mut shorten = et_al(&root).or_else(|| et_al(citation));
+    walk(citation, &macros, Context::default(), 0, &mut Expansion::default(), &mut |node, _| {
+        if node.name == "name" {
+            shorten = et_al(node).or(shorten);
+        }
+    });
+    let shorten = shorten.and_then(|(min, use_first)| {
+        if use_first == 0 || use_first > min {
+            warnings.push(format!("Ignored et-al-min=\"{}\" with et-al-use-first=\"{}\"", min, use_first));
+            return None;
+        }
+        Some(EtAlConfig { min, use_first })
+    });
+    intent.author_format = Some(NameOptions { form: name_form, et_al: shorten });
+
+    intent.citation_preset = locator_prefix.map(|prefix| match prefix.trim_start().chars().next() {
+        Some(':') => "colon-locator",
+        Some(',') => "comma-sep",
+        _ => "minimal",
+    }.to_string());
+
+    // Bibliography
+    intent.has_bibliography = Some(bibliography.is_some());
+    if let Some(bibliography) = bibliography {
+        let mut year_wrapped = false;
+        if let Some(layout) = bibliography.child("layout") {
+            walk(layout, &macros, Context::default(), 0, &mut Expansion::default(), &mut |node, context| {
+                if node.name == "date" && node.attr("variable") == Some("issued") && context.parenthesized {
+                    year_wrapped = true;
+                }
+            });
+        }
+        if intent.class == Some(CitationClass::AuthorDate) {
+            intent.bibliography_preset = Some(if year_wrapped { "year-wrapped" } else { "flat" }.to_string());
+        }
+
+        intent.bibliography_sort = Some(match bibliography.child("sort").and_then(|sort| sort.child("key")) {
+            None => BibliographySort::CitationOrder,
+            Some(key) => sort_for_key(key, &macros).unwrap_or_else(|| {
+                warnings.push("Unrecognised bibliography sort key; sorting by author".to_string());
+                BibliographySort::Author
+            }),
+        });
+        intent.subsequent_author_substitute = bibliography.attr("subsequent-author-substitute").map(str::to_string);
+    }
+
+    // Report what the presets can't reproduce: every CSL template is dropped
+    warnings.push("The <citation> layout was replaced by the nearest CSLN preset layout".to_string());
+    if bibliography.is_some() {
+        warnings.push("The <bibliography> layout was replaced by the nearest CSLN preset layout".to_string());
+    }
+    let mut macro_names: Vec<&str> = macros.keys().copied().collect();
+    macro_names.sort();
+    for name in macro_names {
+        warnings.push(format!("Macro \"{}\" was dropped", name));
+    }
+    let conditionals = root.count("choose");
+    if conditionals > 0 {
+        warnings.push(format!("{} type-specific conditionals (<choose>) were not carried over", conditionals));
+    }
+    if root.child("locale").is_some() {
+        warnings.push("Locale overrides (<locale>) were dropped".to_string());
+    }
+    if citation.attrs.iter().any(|(k, _)| k.starts_with("disambiguate")) {
+        warnings.push("Disambiguation settings were replaced by the preset's defaults".to_string());
+    }
+
+    let mut style = intent.to_style();
+    let text_of = |name: &str| info.and_then(|info| info.child(name)).map(|e| e.text.trim().to_string()).filter(|t| !t.is_empty());
+    style.info.title = text_of("title").or(style.info.title);
+    style.info.id = text_of("id").or(style.info.id);
+
+    Ok(CslImport { style, intent, warnings })
+}
+
+fn sort_for_key(key: &Element, macros: &HashMap<&str, &Element>) -> Option<BibliographySort> {
+    let variable = match (key.attr("variable"), key.attr("macro")) {
+        (Some(variable), _) => variable.to_string(),
+        // Sort by whatever the macro renders first
+        (None, Some(name)) => {
+            let mut first = None;
+            walk(macros.get(name)?, macros, Context::default(), 0, &mut Expansion::default(), &mut |node, _| {
+                if first.is_none() && matches!(node.name.as_str(), "names" | "date" | "text") {
+                    first = node.attr("variable").map(str::to_string);
+                }
+            });
+            first?
+        },
+        (None, None) => return None,
+    };
+    match variable.split_whitespace().next()? {
+        "author" | "editor" | "translator" | "composer" | "director" => Some(BibliographySort::Author),
+        "issued" => Some(BibliographySort::Date),
+        "title" => Some(BibliographySort::Title),
+        "citation-number" => Some(BibliographySort::CitationOrder),
+        _ => None,
+    }
+}
+
+/// A CSL 1.0 style generated from CSLN.
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct CslExport {
+    pub xml: String,
+    /// Features of the CSLN style that CSL 1.0 cannot express.
+    pub lost_features: Vec<String>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Which fixed CSL layout a preset is exported as.
+#[derive(Clone, Copy, PartialEq)]
+enum Layout {
+    Apa,
+    ChicagoAuthorDate,
+    Vancouver,
+    Note,
+}
+
+impl Layout {
+    fn for_preset(preset: Option<&csln_core::TemplatePreset>, part: &str, lost: &mut Vec<String>) -> Self {
+        match preset {
+            Some(csln_core::TemplatePreset::Apa) => Layout::Apa,
+            Some(csln_core::TemplatePreset::ChicagoAuthorDate) => Layout::ChicagoAuthorDate,
+            Some(csln_core::TemplatePreset::Vancouver) => Layout::Vancouver,
+            Some(other) => {
+                lost.push(format!("The {:?} {} preset has no CSL layout; exported with the APA layout", other, part));
+                Layout::Apa
+            },
+            None => {
+                lost.push(format!("The custom {} template is not exported; used the APA layout", part));
+                Layout::Apa
+            },
+        }
+    }
+}
+
+const MACROS: &str = r#"  <macro name="author">
+    <names variable="author">
+      <name name-as-sort-order="first" and="symbol" initialize-with=". " delimiter=", " delimiter-precedes-last="always"/>
+      <substitute>
+        <names variable="editor"/>
+        <text variable="title"/>
+      </substitute>
+    </names>
+  </macro>
+  <macro name="author-short">
+    <names variable="author">
+      <name form="short" and="symbol" delimiter=", "/>
+      <substitute>
+        <names variable="editor"/>
+        <text variable="title" form="short"/>
+      </substitute>
+    </names>
+  </macro>
+  <macro name="author-note">
+    <names variable="author">
+      <name and="text" delimiter=", "/>
+      <substitute>
+        <names variable="editor"/>
+      </substitute>
+    </names>
+  </macro>
+  <macro name="issued-year">
+    <choose>
+      <if variable="issued">
+        <date variable="issued">
+          <date-part name="year"/>
+        </date>
+      </if>
+      <else>
+        <text term="no date" form="short"/>
+      </else>
+    </choose>
+  </macro>
+  <macro name="title">
+    <choose>
+      <if type="book report" match="any">
+        <text variable="title" font-style="italic"/>
+      </if>
+      <else>
+        <text variable="title"/>
+      </else>
+    </choose>
+  </macro>
+  <macro name="container">
+    <group delimiter=", ">
+      <text variable="container-title" font-style="italic"/>
+      <group>
+        <text variable="volume"/>
+        <text variable="issue" prefix="(" suffix=")"/>
+      </group>
+      <text variable="page"/>
+    </group>
+  </macro>
+  <macro name="publisher">
+    <group delimiter=": ">
+      <text variable="publisher-place"/>
+      <text variable="publisher"/>
+    </group>
+  </macro>
+  <macro name="access">
+    <choose>
+      <if variable="DOI">
+        <text variable="DOI" prefix="https://doi.org/"/>
+      </if>
+      <else-if variable="URL">
+        <text variable="URL"/>
+      </else-if>
+    </choose>
+  </macro>
+"#;
+
+fn locator(citation_preset: Option<&str>) -> String {
+    let prefix = match citation_preset {
+        Some("colon-locator") => ": ",
+        Some("minimal") => " ",
+        _ => ", ",
+    };
+    format!(
+        "        <group prefix=\"{}\" delimiter=\" \">\n          <label variable=\"locator\" form=\"short\"/>\n          <text variable=\"locator\"/>\n        </group>\n",
+        prefix
+    )
+}
+
+fn citation_layout(layout: Layout, citation_preset: Option<&str>) -> String {
+    match layout {
+        Layout::Vancouver => "    <layout prefix=\"[\" suffix=\"]\" delimiter=\",\">\n      <text variable=\"citation-number\"/>\n    </layout>\n".to_string(),
+        Layout::Note => format!(
+            "    <layout suffix=\".\" delimiter=\"; \">\n      <group delimiter=\", \">\n        <text macro=\"author-note\"/>\n        <text macro=\"title\"/>\n        <text macro=\"issued-year\"/>\n      </group>\n{}    </layout>\n",
+            locator(citation_preset).replace("        ", "      ")
+        ),
+        Layout::Apa | Layout::ChicagoAuthorDate => format!(
+            "    <layout prefix=\"(\" suffix=\")\" delimiter=\"; \">\n      <group>\n        <group delimiter=\"{}\">\n          <text macro=\"author-short\"/>\n          <text macro=\"issued-year\"/>\n        </group>\n{}      </group>\n    </layout>\n",
+            if layout == Layout::Apa { ", " } else { " " },
+            locator(citation_preset)
+        ),
+    }
+}
+
+fn bibliography_layout(layout: Layout) -> String {
+    match layout {
+        Layout::Vancouver => r#"    <layout>
+      <text variable="citation-number" suffix=". "/>
+      <group delimiter=". " suffix=".">
+        <names variable="author">
+          <name name-as-sort-order="all" initialize-with="" sort-separator=" " delimiter=", "/>
+        </names>
+        <text variable="title"/>
+        <text macro="container"/>
+        <text macro="issued-year"/>
+      </group>
+    </layout>
+"#.to_string(),
+        _ => format!(
+            "    <layout>\n      <group delimiter=\". \" suffix=\".\">\n        <text macro=\"author\"/>\n        <text macro=\"issued-year\"{}/>\n        <text macro=\"title\"/>\n        <text macro=\"container\"/>\n        <text macro=\"publisher\"/>\n      </group>\n      <text macro=\"access\" prefix=\" \"/>\n    </layout>\n",
+            if layout == Layout::Apa { " prefix=\"(\" suffix=\")\"" } else { "" }
+        ),
+    }
+}
+
+fn et_al_attrs(options: Option<&csln_core::options::Config>) -> String {
+    options.and_then(|o| o.contributors.as_ref())
+        .and_then(|c| c.shorten.as_ref())
+        .map(|s| format!(" et-al-min=\"{}\" et-al-use-first=\"{}\"", s.min, s.use_first))
+        .unwrap_or_default()
+}
+
+fn sort_keys(sort: BibliographySort) -> &'static str {
+    match sort {
+        BibliographySort::Author => "      <key macro=\"author\"/>\n      <key variable=\"issued\"/>\n",
+        BibliographySort::Date => "      <key variable=\"issued\"/>\n      <key macro=\"author\"/>\n",
+        BibliographySort::Title => "      <key variable=\"title\"/>\n      <key macro=\"author\"/>\n",
+        BibliographySort::CitationOrder => "      <key variable=\"citation-number\"/>\n",
+    }
+}
+
+/// Exports a CSLN style as CSL 1.0 XML.
+///
+/// Each template preset maps to a fixed CSL layout. `intent`, when given,
+/// supplies what the CSLN style doesn't record: note vs in-text class,
+/// locator punctuation, bibliography sort and author substitution.
+pub fn export(style: &csln_core::Style, intent: Option<&StyleIntent>) -> CslExport {
+    let mut lost = Vec::new();
+    let class = intent.and_then(|i| i.class.clone());
+    let is_note = matches!(class, Some(CitationClass::Footnote) | Some(CitationClass::Endnote));
+    let citation_preset = intent.and_then(|i| i.citation_preset.as_deref());
+
+    let citation = style.citation.as_ref();
+    let citation_layout_kind = if is_note {
+        Layout::Note
+    } else {
+        Layout::for_preset(citation.and_then(|c| c.use_preset.as_ref()), "citation", &mut lost)
+    };
+    let (format, class_attr) = match citation_layout_kind {
+        Layout::Note => ("note", "note"),
+        Layout::Vancouver => ("numeric", "in-text"),
+        Layout::Apa | Layout::ChicagoAuthorDate => ("author-date", "in-text"),
+    };
+
+    let title = style.info.title.clone().unwrap_or_else(|| "Custom Style".to_string());
+    let id = style.info.id.clone().unwrap_or_else(|| "custom-style".to_string());
+    let id = if id.contains(':') { id } else { format!("urn:csln:{}", id) };
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str(&format!(
+        "<style xmlns=\"http://purl.org/net/xbiblio/csl\" class=\"{}\" version=\"1.0\" demote-non-dropping-particle=\"sort-only\" default-locale=\"en-US\">\n",
+        class_attr
+    ));
+    xml.push_str(&format!(
+        "  <info>\n    <title>{}</title>\n    <id>{}</id>\n    <category citation-format=\"{}\"/>\n    <updated>{}</updated>\n  </info>\n",
+        escape(&title),
+        escape(&id),
+        format,
+        timestamp()
+    ));
+    xml.push_str(MACROS);
+
+    let disambiguate = match citation_layout_kind {
+        Layout::Apa | Layout::ChicagoAuthorDate => " disambiguate-add-year-suffix=\"true\" disambiguate-add-givenname=\"true\"",
+        Layout::Vancouver => " collapse=\"citation-number\"",
+        Layout::Note => "",
+    };
+    xml.push_str(&format!(
+        "  <citation{}{}>\n{}  </citation>\n",
+        et_al_attrs(citation.and_then(|c| c.options.as_ref())),
+        disambiguate,
+        citation_layout(citation_layout_kind, citation_preset)
+    ));
+
+    if let Some(bibliography) = &style.bibliography {
+        let layout = Layout::for_preset(bibliography.use_preset.as_ref(), "bibliography", &mut lost);
+        let sort = match (intent, layout) {
+            (Some(intent), _) => intent.effective_bibliography_sort(),
+            (None, Layout::Vancouver) => BibliographySort::CitationOrder,
+            (None, _) => BibliographySort::Author,
+        };
+        let substitute = intent.and_then(|i| i.subsequent_author_substitute.as_deref())
+            .map(|s| format!(" subsequent-author-substitute=\"{}\"", escape(s)))
+            .unwrap_or_default();
+        let align = if layout == Layout::Vancouver { " second-field-align=\"flush\"" } else { " hanging-indent=\"true\"" };
+        xml.push_str(&format!(
+            "  <bibliography{}{}{}>\n    <sort>\n{}    </sort>\n{}  </bibliography>\n",
+            et_al_attrs(bibliography.options.as_ref()),
+            align,
+            substitute,
+            sort_keys(sort),
+            bibliography_layout(layout)
+        ));
+    }
+    xml.push_str("</style>\n");
+
+    if intent.and_then(|i| i.bibliography_grouping).is_some() {
+        lost.push("CSL 1.0 cannot group bibliography entries under headings; entries are listed together".to_string());
+    }
+    if matches!(class, Some(CitationClass::Endnote)) {
+        lost.push("CSL 1.0 does not distinguish endnotes from footnotes; exported as a note style".to_string());
+    }
+
+    CslExport { xml, lost_features: lost }
+}
+
+/// The current UTC time in the `xs:dateTime` form CSL's `<updated>` requires.
+fn timestamp() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+}
+
+#[cfg(test)]
+mod csl_tests {
+    use super::*;
+
+    const APA_LIKE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<style xmlns="http://purl.org/net/xbiblio/csl" class="in-text" version="1.0" et-al-min="3" et-al-use-first="1">
+  <info>
+    <title>Example Author-Date</title>
+    <id>http://www.zotero.org/styles/example-author-date</id>
+    <category citation-format="author-date"/>
+    <category field="psychology"/>
+  </info>
+  <macro name="author">
+    <names variable="author"><name form="short"/></names>
+  </macro>
+  <macro name="issued">
+    <group prefix=" (" suffix=").">
+      <date variable="issued"><date-part name="year"/></date>
+    </group>
+  </macro>
+  <citation disambiguate-add-year-suffix="true">
+    <layout prefix="(" suffix=")" delimiter="; ">
+      <group delimiter=" ">
+        <text macro="author"/>
+        <date variable="issued"><date-part name="year"/></date>
+        <group prefix=": "><text variable="locator"/></group>
+      </group>
+    </layout>
+  </citation>
+  <bibliography subsequent-author-substitute="———">
+    <sort><key macro="author"/></sort>
+    <layout>
+      <text macro="author"/>
+      <text macro="issued"/>
+      <text variable="title"/>
+    </layout>
+  </bibliography>
+</style>"#;
+
+    #[test]
+    fn test_import_author_date() {
+        let import = import(APA_LIKE).unwrap();
+        let intent = import.intent;
+        assert_eq!(intent.class, Some(CitationClass::AuthorDate));
+        assert_eq!(intent.field.as_deref(), Some("social_science"));
+        assert_eq!(intent.citation_preset.as_deref(), Some("colon-locator"));
+        assert_eq!(intent.bibliography_preset.as_deref(), Some("year-wrapped"));
+        assert_eq!(intent.bibliography_sort, Some(BibliographySort::Author));
+        assert_eq!(intent.subsequent_author_substitute.as_deref(), Some("———"));
+
+        let names = intent.author_format.unwrap();
+        assert!(matches!(names.form, NameForm::Short));
+        let et_al = names.et_al.unwrap();
+        assert_eq!((et_al.min, et_al.use_first), (3, 1));
+
+        assert_eq!(import.style.info.title.as_deref(), Some("Example Author-Date"));
+        let dropped: Vec<&String> = import.warnings.iter()
+            .filter(|w| w.contains("layout was replaced") || w.starts_with("Macro"))
+            .collect();
+        assert_eq!(dropped, [
+            "The <citation> layout was replaced by the nearest CSLN preset layout",
+            "The <bibliography> layout was replaced by the nearest CSLN preset layout",
+            "Macro \"author\" was dropped",
+            "Macro \"issued\" was dropped",
+        ]);
+    }
+
+    #[test]
+    fn test_import_numeric_and_note() {
+        let numeric = r#"<style class="in-text"><citation><layout><text variable="citation-number"/></layout></citation></style>"#;
+        let intent = import(numeric).unwrap().intent;
+        assert_eq!(intent.class, Some(CitationClass::Numeric));
+        assert_eq!(intent.has_bibliography, Some(false));
+
+        let note = r#"<style class="note"><citation><layout><text variable="title"/></layout></citation></style>"#;
+        assert_eq!(import(note).unwrap().intent.class, Some(CitationClass::Footnote));
+    }
+
+    #[test]
+    fn test_import_survives_recursive_macros() {
+        let recursive = r#"<style class="in-text">
+  <macro name="loop"><text macro="loop"/><text macro="loop"/><names variable="author"/></macro>
+  <citation><layout><text macro="loop"/></layout></citation>
+  <bibliography><sort><key macro="loop"/></sort><layout><text macro="loop"/></layout></bibliography>
+</style>"#;
+        let started = std::time::Instant::now();
+        let intent = import(recursive).unwrap().intent;
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert_eq!(intent.bibliography_sort, Some(BibliographySort::Author));
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let intent = StyleIntent {
+            class: Some(CitationClass::AuthorDate),
+            has_bibliography: Some(true),
+            bibliography_preset: Some("year-wrapped".to_string()),
+            citation_preset: Some("colon-locator".to_string()),
+            author_format: Some(NameOptions { form: NameForm::Short, et_al: Some(EtAlConfig { min: 4, use_first: 2 }) }),
+            bibliography_sort: Some(BibliographySort::Date),
+            ..Default::default()
+        };
+
+        let export = export(&intent.to_style(), Some(&intent));
+        assert!(export.lost_features.is_empty());
+
+        let back = import(&export.xml).unwrap().intent;
+        assert_eq!(back.class, Some(CitationClass::AuthorDate));
+        assert_eq!(back.citation_preset.as_deref(), Some("colon-locator"));
+        assert_eq!(back.bibliography_preset.as_deref(), Some("year-wrapped"));
+        assert_eq!(back.bibliography_sort, Some(BibliographySort::Date));
+        let et_al = back.author_format.unwrap().et_al.unwrap();
+        assert_eq!((et_al.min, et_al.use_first), (4, 2));
+    }
+
+    #[test]
+    fn test_export_reports_lost_features() {
+        let intent = StyleIntent {
+            class: Some(CitationClass::Endnote),
+            has_bibliography: Some(true),
+            bibliography_grouping: Some(crate::BibliographyGrouping::Language),
+            ..Default::default()
+        };
+
+        let export = export(&intent.to_style(), Some(&intent));
+        assert!(export.xml.contains("class=\"note\""));
+        assert_eq!(export.lost_features.len(), 2);
+    }
+
+    #[test]
+    fn test_timestamp_is_xs_date_time() {
+        let stamp = timestamp();
+        assert_eq!(stamp.len(), "2026-01-01T00:00:00+00:00".len());
+        assert!(chrono::DateTime::parse_from_rfc3339(&stamp).is_ok());
+        assert!(stamp.ends_with("+00:00"));
+    }
+
+    #[test]
+    fn test_import_rejects_non_csl() {
+        assert!(matches!(import("<html></html>"), Err(CslError::NotCsl(_))));
+        assert!(matches!(import("<style><citation>"), Err(CslError::Xml(_))));
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+pub mod csl;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
 #[serde(rename_all = "snake_case")]
 pub struct NameOptions {
@@ -44,6 +46,12 @@ pub struct StyleIntent {
     pub bibliography_preset: Option<String>,
     /// Whether to show advanced "detailed" configuration options
     pub detailed_config: Option<bool>,
+    /// Primary sort key for bibliography entries
+    pub bibliography_sort: Option<BibliographySort>,
+    /// How bibliography entries are grouped under headings
+    pub bibliography_grouping: Option<BibliographyGrouping>,
+    /// Replacement for an author list repeated from the previous entry (e.g. "———")
+    pub subsequent_author_substitute: Option<String>,
 }
 
 impl StyleIntent {
@@ -78,9 +86,9 @@ impl StyleIntent {
                     description: Some("Select one or more fields to find appropriate styles.".to_string()),
                 }),
                 vec![
-                    Preview { label: "Humanities".to_string(), html: String::new(), choice_value: serde_json::json!({ "field": "humanities" }) },
-                    Preview { label: "Social Science".to_string(), html: String::new(), choice_value: serde_json::json!({ "field": "social_science" }) },
-                    Preview { label: "Sciences".to_string(), html: String::new(), choice_value: serde_json::json!({ "field": "sciences" }) },
+                    Preview { label: "Humanities".to_string(), html: String::new(), choice_value: StyleIntentPatch { field: Some("humanities".to_string()), ..Default::default() } },
+                    Preview { label: "Social Science".to_string(), html: String::new(), choice_value: StyleIntentPatch { field: Some("social_science".to_string()), ..Default::default() } },
+                    Preview { label: "Sciences".to_string(), html: String::new(), choice_value: StyleIntentPatch { field: Some("sciences".to_string()), ..Default::default() } },
                 ]
             )
         // 2. Style Type Selection
@@ -90,16 +98,16 @@ impl StyleIntent {
             
             match field.as_str() {
                 "humanities" => {
-                    options.push(Preview { label: "Footnote".to_string(), html: String::new(), choice_value: serde_json::json!({ "class": "footnote" }) });
-                    options.push(Preview { label: "Endnote".to_string(), html: String::new(), choice_value: serde_json::json!({ "class": "endnote" }) });
-                    options.push(Preview { label: "Author-Date".to_string(), html: String::new(), choice_value: serde_json::json!({ "class": "author_date" }) });
+                    options.push(Preview { label: "Footnote".to_string(), html: String::new(), choice_value: StyleIntentPatch { class: Some(CitationClass::Footnote), ..Default::default() } });
+                    options.push(Preview { label: "Endnote".to_string(), html: String::new(), choice_value: StyleIntentPatch { class: Some(CitationClass::Endnote), ..Default::default() } });
+                    options.push(Preview { label: "Author-Date".to_string(), html: String::new(), choice_value: StyleIntentPatch { class: Some(CitationClass::AuthorDate), ..Default::default() } });
                 },
                 "social_science" => {
-                    options.push(Preview { label: "Author-Date".to_string(), html: String::new(), choice_value: serde_json::json!({ "class": "author_date" }) });
+                    options.push(Preview { label: "Author-Date".to_string(), html: String::new(), choice_value: StyleIntentPatch { class: Some(CitationClass::AuthorDate), ..Default::default() } });
                 },
                 _ => { // Sciences
-                    options.push(Preview { label: "Author-Date".to_string(), html: String::new(), choice_value: serde_json::json!({ "class": "author_date" }) });
-                    options.push(Preview { label: "Numeric".to_string(), html: String::new(), choice_value: serde_json::json!({ "class": "numeric" }) });
+                    options.push(Preview { label: "Author-Date".to_string(), html: String::new(), choice_value: StyleIntentPatch { class: Some(CitationClass::AuthorDate), ..Default::default() } });
+                    options.push(Preview { label: "Numeric".to_string(), html: String::new(), choice_value: StyleIntentPatch { class: Some(CitationClass::Numeric), ..Default::default() } });
                 }
             }
 
@@ -126,17 +134,17 @@ impl StyleIntent {
                             Preview { 
                                 label: "(Smith and Jones, 2023: 34)".to_string(), 
                                 html: String::new(), 
-                                choice_value: serde_json::json!({ "citation_preset": "colon-locator" }) 
+                                choice_value: StyleIntentPatch { citation_preset: Some("colon-locator".to_string()), ..Default::default() } 
                             },
                             Preview { 
                                 label: "(Smith and Jones, 2023, p.34)".to_string(), 
                                 html: String::new(), 
-                                choice_value: serde_json::json!({ "citation_preset": "comma-sep" }) 
+                                choice_value: StyleIntentPatch { citation_preset: Some("comma-sep".to_string()), ..Default::default() } 
                             },
                             Preview { 
                                 label: "(Smith and Jones 2023, 34)".to_string(), 
                                 html: String::new(), 
-                                choice_value: serde_json::json!({ "citation_preset": "minimal" }) 
+                                choice_value: StyleIntentPatch { citation_preset: Some("minimal".to_string()), ..Default::default() } 
                             },
                         ]
                     )
@@ -153,12 +161,12 @@ impl StyleIntent {
                             Preview { 
                                 label: "Smith, J. (2023). Title...".to_string(), 
                                 html: String::new(), 
-                                choice_value: serde_json::json!({ "bibliography_preset": "year-wrapped", "has_bibliography": true }) 
+                                choice_value: StyleIntentPatch { bibliography_preset: Some("year-wrapped".to_string()), has_bibliography: Some(true), ..Default::default() } 
                             },
                             Preview { 
                                 label: "Smith, J. 2023. Title...".to_string(), 
                                 html: String::new(), 
-                                choice_value: serde_json::json!({ "bibliography_preset": "flat", "has_bibliography": true }) 
+                                choice_value: StyleIntentPatch { bibliography_preset: Some("flat".to_string()), has_bibliography: Some(true), ..Default::default() } 
                             },
                         ]
                     )
@@ -172,8 +180,8 @@ impl StyleIntent {
                             description: Some("The presets cover 90% of cases. Do you need to tweak granular details like author initials or et al. rules?".to_string()),
                         }),
                         vec![
-                            Preview { label: "No, presets are fine".to_string(), html: String::new(), choice_value: serde_json::json!({ "detailed_config": false }) },
-                            Preview { label: "Yes, show detailed config".to_string(), html: String::new(), choice_value: serde_json::json!({ "detailed_config": true }) },
+                            Preview { label: "No, presets are fine".to_string(), html: String::new(), choice_value: StyleIntentPatch { detailed_config: Some(false), ..Default::default() } },
+                            Preview { label: "Yes, show detailed config".to_string(), html: String::new(), choice_value: StyleIntentPatch { detailed_config: Some(true), ..Default::default() } },
                         ]
                     )
                 },
@@ -189,16 +197,32 @@ impl StyleIntent {
                             Preview { 
                                 label: "Standard (APA-style et al.)".to_string(), 
                                 html: String::new(), 
-                                choice_value: serde_json::json!({ "author_format": { "form": "long", "et_al": { "min": 3, "use_first": 1 } } }) 
+                                choice_value: StyleIntentPatch { author_format: Some(NameOptions { form: NameForm::Long, et_al: Some(EtAlConfig { min: 3, use_first: 1 }) }), ..Default::default() } 
                             },
                             Preview { 
                                 label: "Always show all authors".to_string(), 
                                 html: String::new(), 
-                                choice_value: serde_json::json!({ "author_format": { "form": "long", "et_al": null } }) 
+                                choice_value: StyleIntentPatch { author_format: Some(NameOptions { form: NameForm::Long, et_al: None }), ..Default::default() } 
                             },
                         ]
                     )
                 },
+                // 3e. Author-Date: Bibliography Order (only if detailed_config is true)
+                CitationClass::AuthorDate if self.detailed_config == Some(true) && self.bibliography_sort.is_none() => {
+                    (
+                        Some(Question {
+                            id: "bibliography_sort".to_string(),
+                            text: "How should the bibliography be ordered?".to_string(),
+                            description: Some("Repeated authors can also be replaced with a dash.".to_string()),
+                        }),
+                        vec![
+                            Preview { label: "By author, then year".to_string(), html: String::new(), choice_value: StyleIntentPatch { bibliography_sort: Some(BibliographySort::Author), ..Default::default() } },
+                            Preview { label: "By author, with ——— for repeats".to_string(), html: String::new(), choice_value: StyleIntentPatch { bibliography_sort: Some(BibliographySort::Author), subsequent_author_substitute: Some("———".to_string()), ..Default::default() } },
+                            Preview { label: "By year".to_string(), html: String::new(), choice_value: StyleIntentPatch { bibliography_sort: Some(BibliographySort::Date), ..Default::default() } },
+                            Preview { label: "Grouped by type".to_string(), html: String::new(), choice_value: StyleIntentPatch { bibliography_sort: Some(BibliographySort::Author), bibliography_grouping: Some(BibliographyGrouping::ReferenceType), ..Default::default() } },
+                        ]
+                    )
+                },
                 
                 // --- Other Classes ---
 
@@ -211,8 +235,8 @@ impl StyleIntent {
                             description: Some("Note formatting typically changes if a bibliography is present.".to_string()),
                         }),
                         vec![
-                            Preview { label: "Yes, include bibliography".to_string(), html: String::new(), choice_value: serde_json::json!({ "has_bibliography": true }) },
-                            Preview { label: "No, notes only".to_string(), html: String::new(), choice_value: serde_json::json!({ "has_bibliography": false }) },
+                            Preview { label: "Yes, include bibliography".to_string(), html: String::new(), choice_value: StyleIntentPatch { has_bibliography: Some(true), ..Default::default() } },
+                            Preview { label: "No, notes only".to_string(), html: String::new(), choice_value: StyleIntentPatch { has_bibliography: Some(false), ..Default::default() } },
                         ]
                     )
                 },
@@ -225,9 +249,9 @@ impl StyleIntent {
                             description: None,
                         }),
                         vec![
-                            Preview { label: "Square Brackets [1]".to_string(), html: String::new(), choice_value: serde_json::json!({ "author_format": { "form": "short", "et_al": null } }) },
-                            Preview { label: "Parentheses (1)".to_string(), html: String::new(), choice_value: serde_json::json!({ "author_format": { "form": "long", "et_al": null } }) },
-                            Preview { label: "Superscript ¹".to_string(), html: String::new(), choice_value: serde_json::json!({ "author_format": { "form": "long", "et_al": { "min": 1, "use_first": 1 } } }) },
+                            Preview { label: "Square Brackets [1]".to_string(), html: String::new(), choice_value: StyleIntentPatch { author_format: Some(NameOptions { form: NameForm::Short, et_al: None }), ..Default::default() } },
+                            Preview { label: "Parentheses (1)".to_string(), html: String::new(), choice_value: StyleIntentPatch { author_format: Some(NameOptions { form: NameForm::Long, et_al: None }), ..Default::default() } },
+                            Preview { label: "Superscript ¹".to_string(), html: String::new(), choice_value: StyleIntentPatch { author_format: Some(NameOptions { form: NameForm::Long, et_al: Some(EtAlConfig { min: 1, use_first: 1 }) }), ..Default::default() } },
                         ]
                     )
                 },
@@ -240,8 +264,8 @@ impl StyleIntent {
                             description: None,
                         }),
                         vec![
-                            Preview { label: "Standard".to_string(), html: String::new(), choice_value: serde_json::json!({ "author_format": { "form": "long", "et_al": { "min": 3, "use_first": 1 } } }) },
-                            Preview { label: "Full".to_string(), html: String::new(), choice_value: serde_json::json!({ "author_format": { "form": "long", "et_al": null } }) },
+                            Preview { label: "Standard".to_string(), html: String::new(), choice_value: StyleIntentPatch { author_format: Some(NameOptions { form: NameForm::Long, et_al: Some(EtAlConfig { min: 3, use_first: 1 }) }), ..Default::default() } },
+                            Preview { label: "Full".to_string(), html: String::new(), choice_value: StyleIntentPatch { author_format: Some(NameOptions { form: NameForm::Long, et_al: None }), ..Default::default() } },
                         ]
                     )
                 },
@@ -258,6 +282,7 @@ impl StyleIntent {
             in_text_preview: None,
             note_preview: None,
             bibliography_preview: None,
+            fragments: Vec::new(),
         }
     }
 
@@ -268,6 +293,16 @@ impl StyleIntent {
         String::new()
     }
 
+    /// The bibliography sort to use, defaulting to citation order for
+    /// numeric styles and author order otherwise.
+    pub fn effective_bibliography_sort(&self) -> BibliographySort {
+        match (self.bibliography_sort, &self.class) {
+            (Some(sort), _) => sort,
+            (None, Some(CitationClass::Numeric)) => BibliographySort::CitationOrder,
+            (None, _) => BibliographySort::Author,
+        }
+    }
+
     /// Converts the current intent into a `csln_core::Style` struct.
     pub fn to_style(&self) -> csln_core::Style {
          // Construct the basic metadata for the new style
@@ -331,7 +366,7 @@ impl StyleIntent {
              if self.has_bibliography.unwrap_or(false) {
                  style.bibliography = Some(csln_core::BibliographySpec {
                      use_preset: Some(p),
-                     options,
+                     options: Some(self.bibliography_options(options)),
                      ..Default::default()
                  });
              }
@@ -340,6 +375,41 @@ impl StyleIntent {
         style
     }
 
+    /// Adds the bibliography sort and author substitution to the options
+    /// shared with the citation.
+    ///
+    /// CSLN groups entries only by sort keys, so `bibliography_grouping`
+    /// (by reference type or language) has no equivalent here; it is
+    /// applied when previews are rendered and reported as lost on export.
+    fn bibliography_options(&self, shared: Option<csln_core::options::Config>) -> csln_core::options::Config {
+        use csln_core::options::{BibliographyConfig, Processing, ProcessingCustom, Sort, SortKey, SortSpec};
+
+        let keys = match self.effective_bibliography_sort() {
+            BibliographySort::Author => vec![SortKey::Author, SortKey::Year, SortKey::Title],
+            BibliographySort::Date => vec![SortKey::Year, SortKey::Author, SortKey::Title],
+            BibliographySort::Title => vec![SortKey::Title, SortKey::Author, SortKey::Year],
+            // No sort keys leaves entries in the order they were first cited
+            BibliographySort::CitationOrder => Vec::new(),
+        };
+        let sort = (!keys.is_empty()).then(|| Sort {
+            template: keys.into_iter().map(|key| SortSpec { key, ascending: true }).collect(),
+            ..Default::default()
+        });
+
+        let mut options = shared.unwrap_or_default();
+        options.processing = Some(Processing::Custom(ProcessingCustom {
+            sort,
+            ..Default::default()
+        }));
+        if let Some(substitute) = &self.subsequent_author_substitute {
+            options.bibliography = Some(BibliographyConfig {
+                subsequent_author_substitute: Some(substitute.clone()),
+                ..Default::default()
+            });
+        }
+        options
+    }
+
     /// Generates a complete CSLN YAML string based on the current intent.
     pub fn generate_csln(&self) -> String {
         let style = self.to_style();
@@ -389,6 +459,125 @@ mod intent_tests {
         assert_eq!(shorten.min, 3);
         assert_eq!(shorten.use_first, 1);
     }
+
+    #[test]
+    fn test_bibliography_sort_default() {
+        let mut intent = StyleIntent::default();
+        intent.class = Some(CitationClass::Numeric);
+        assert_eq!(intent.effective_bibliography_sort(), BibliographySort::CitationOrder);
+
+        intent.class = Some(CitationClass::AuthorDate);
+        assert_eq!(intent.effective_bibliography_sort(), BibliographySort::Author);
+
+        intent.bibliography_sort = Some(BibliographySort::Title);
+        assert_eq!(intent.effective_bibliography_sort(), BibliographySort::Title);
+    }
+
+    #[test]
+    fn test_to_style_bibliography_options() {
+        let mut intent = StyleIntent::default();
+        intent.class = Some(CitationClass::AuthorDate);
+        intent.has_bibliography = Some(true);
+        intent.bibliography_sort = Some(BibliographySort::Date);
+        intent.subsequent_author_substitute = Some("———".to_string());
+
+        let opts = intent.to_style().bibliography.unwrap().options.unwrap();
+        let sort = match opts.processing {
+            Some(csln_core::options::Processing::Custom(custom)) => custom.sort.unwrap(),
+            other => panic!("expected custom processing, got {:?}", other),
+        };
+        let keys: Vec<_> = sort.template.into_iter().map(|spec| spec.key).collect();
+        assert_eq!(keys, [csln_core::options::SortKey::Year, csln_core::options::SortKey::Author, csln_core::options::SortKey::Title]);
+        assert_eq!(opts.bibliography.unwrap().subsequent_author_substitute.as_deref(), Some("———"));
+
+        // Numeric styles keep citation order
+        intent.class = Some(CitationClass::Numeric);
+        intent.bibliography_sort = None;
+        let opts = intent.to_style().bibliography.unwrap().options.unwrap();
+        match opts.processing {
+            Some(csln_core::options::Processing::Custom(custom)) => assert!(custom.sort.is_none()),
+            other => panic!("expected custom processing, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_intent_field_for_component() {
+        let class = CitationClass::AuthorDate;
+        assert_eq!(class.intent_field_for("contributor:author", PreviewContext::Citation), Some("author_format"));
+        assert_eq!(class.intent_field_for("date:issued", PreviewContext::Citation), Some("citation_preset"));
+        assert_eq!(class.intent_field_for("title:primary", PreviewContext::Bibliography), Some("bibliography_preset"));
+        assert_eq!(class.intent_field_for("title:primary", PreviewContext::Citation), None);
+        assert_eq!(CitationClass::Numeric.intent_field_for("date:issued", PreviewContext::Citation), None);
+
+        let notes = CitationClass::Footnote;
+        assert_eq!(notes.intent_field_for("locator", PreviewContext::Note), Some("citation_preset"));
+        assert_eq!(notes.intent_field_for("contributor:author", PreviewContext::Note), Some("author_format"));
+        assert_eq!(notes.intent_field_for("title:primary", PreviewContext::Note), None);
+    }
+
+    #[test]
+    fn test_note_positions() {
+        let ids: Vec<String> = ["a", "b", "a", "a", "b"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            NotePosition::sequence(&ids),
+            vec![
+                NotePosition::First,
+                NotePosition::First,
+                NotePosition::Subsequent,
+                NotePosition::Ibid,
+                NotePosition::Subsequent,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_patch_apply() {
+        let mut intent = StyleIntent::default();
+        intent.field = Some("sciences".to_string());
+        intent.class = Some(CitationClass::AuthorDate);
+
+        let patch: StyleIntentPatch = serde_json::from_value(
+            serde_json::json!({ "bibliography_preset": "flat", "has_bibliography": true })
+        ).unwrap();
+        let patched = patch.apply(&intent).unwrap();
+
+        assert_eq!(patched.field.as_deref(), Some("sciences"));
+        assert_eq!(patched.class, Some(CitationClass::AuthorDate));
+        assert_eq!(patched.bibliography_preset.as_deref(), Some("flat"));
+        assert_eq!(patched.has_bibliography, Some(true));
+    }
+
+    #[test]
+    fn test_patch_rejects_invalid() {
+        let intent = StyleIntent::default();
+
+        let patch = StyleIntentPatch { citation_preset: Some("bogus".to_string()), ..Default::default() };
+        assert!(matches!(patch.apply(&intent), Err(PatchError::UnknownPreset { .. })));
+
+        let patch = StyleIntentPatch {
+            author_format: Some(NameOptions { form: NameForm::Long, et_al: Some(EtAlConfig { min: 2, use_first: 3 }) }),
+            ..Default::default()
+        };
+        assert!(matches!(patch.apply(&intent), Err(PatchError::InvalidEtAl { min: 2, use_first: 3 })));
+
+        assert!(serde_json::from_value::<StyleIntentPatch>(serde_json::json!({ "colour": "red" })).is_err());
+    }
+
+    #[test]
+    fn test_choices_apply_cleanly() {
+        let mut intent = StyleIntent::default();
+        // Walk the wizard, always taking the first choice
+        for _ in 0..10 {
+            let package = intent.decide();
+            for preview in &package.previews {
+                assert!(preview.choice_value.apply(&intent).is_ok(), "choice '{}' failed", preview.label);
+            }
+            match package.previews.first() {
+                Some(first) => intent = first.choice_value.apply(&intent).unwrap(),
+                None => break,
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Type)]
@@ -400,6 +589,67 @@ pub enum CitationClass {
     Numeric,
 }
 
+impl CitationClass {
+    /// Returns the `StyleIntent` field (which is also the wizard question id)
+    /// that controls a CSLN template component rendered in the given context.
+    pub fn intent_field_for(&self, component: &str, context: PreviewContext) -> Option<&'static str> {
+        let kind = component.split(':').next().unwrap_or(component);
+        match (kind, context) {
+            ("contributor", _) | ("citation-number", _) => Some("author_format"),
+            ("locator", _) => Some("citation_preset"),
+            ("date", PreviewContext::Citation) if self == &CitationClass::AuthorDate => Some("citation_preset"),
+            ("date", PreviewContext::Bibliography) | ("title", PreviewContext::Bibliography) => Some("bibliography_preset"),
+            ("group", PreviewContext::Bibliography) => Some("bibliography_grouping"),
+            ("substitute", PreviewContext::Bibliography) => Some("subsequent_author_substitute"),
+            // Other parts of a note follow from the class as a whole, and
+            // sending a click there would clear every later choice
+            _ => None,
+        }
+    }
+}
+
+/// Where in a preview a fragment was rendered.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewContext {
+    Citation,
+    Note,
+    Bibliography,
+}
+
+/// A piece of rendered preview output traced back to what produced it,
+/// so a click on it can open the wizard question that controls it.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct PreviewFragment {
+    pub context: PreviewContext,
+    pub text: String,
+    /// CSLN template component path, e.g. "contributor:author".
+    pub component: String,
+    pub reference_id: Option<String>,
+    /// The `StyleIntent` field responsible, if any.
+    pub intent_field: Option<String>,
+}
+
+/// Primary sort key for bibliography entries. Ties are broken by the
+/// remaining keys in author, date, title order.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum BibliographySort {
+    Author,
+    Date,
+    Title,
+    /// Order of first citation, as used by numeric styles.
+    CitationOrder,
+}
+
+/// How bibliography entries are grouped under headings.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum BibliographyGrouping {
+    ReferenceType,
+    Language,
+}
+
 /// A package returned by the backend containing everything the frontend
 /// needs to render the next step in the wizard.
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -417,11 +667,55 @@ pub struct DecisionPackage {
     /// Specific citation example (for author-date styles)
     pub in_text_preview: Option<String>,
 
-    /// Specific note example (for note-based styles)
-    pub note_preview: Option<String>,
+    /// Numbered notes as they would appear at the foot of the page (for note-based styles)
+    pub note_preview: Option<Vec<NotePreview>>,
 
     /// Bibliography entry examples
     pub bibliography_preview: Option<String>,
+
+    /// Traced fragments of the previews above, for click-to-edit
+    pub fragments: Vec<PreviewFragment>,
+}
+
+/// Where a note falls relative to earlier citations of the same source.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum NotePosition {
+    /// The first citation of a source, rendered in full.
+    First,
+    /// A later citation of a source, usually rendered in short form.
+    Subsequent,
+    /// A citation of the same source as the immediately preceding note.
+    Ibid,
+}
+
+impl NotePosition {
+    /// Classifies each cited reference id, given in note order.
+    pub fn sequence(ids: &[String]) -> Vec<NotePosition> {
+        ids.iter()
+            .enumerate()
+            .map(|(i, id)| {
+                if i > 0 && &ids[i - 1] == id {
+                    NotePosition::Ibid
+                } else if ids[..i].contains(id) {
+                    NotePosition::Subsequent
+                } else {
+                    NotePosition::First
+                }
+            })
+            .collect()
+    }
+}
+
+/// A single rendered footnote or endnote.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct NotePreview {
+    /// The note number, starting at 1.
+    pub number: u32,
+    pub reference_id: String,
+    pub position: NotePosition,
+    /// The note text, without the number.
+    pub text: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -435,10 +729,102 @@ pub struct Question {
 pub struct Preview {
     pub label: String,
     pub html: String,
-    /// The JSON value (serialized) that will be applied to the intent if this option is chosen
-    pub choice_value: serde_json::Value,
+    /// The patch that will be applied to the intent if this option is chosen
+    pub choice_value: StyleIntentPatch,
 }
 
+/// Known values for `StyleIntent::citation_preset`.
+pub const CITATION_PRESETS: [&str; 3] = ["colon-locator", "comma-sep", "minimal"];
+
+/// Known values for `StyleIntent::bibliography_preset`.
+pub const BIBLIOGRAPHY_PRESETS: [&str; 2] = ["year-wrapped", "flat"];
+
+/// A partial update to a `StyleIntent`.
+/// Fields left as `None` keep their current value when the patch is applied.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, Type)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct StyleIntentPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_archetype: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<CitationClass>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_format: Option<NameOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_bibliography: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub citation_preset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bibliography_preset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detailed_config: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bibliography_sort: Option<BibliographySort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bibliography_grouping: Option<BibliographyGrouping>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subsequent_author_substitute: Option<String>,
+}
+
+impl StyleIntentPatch {
+    /// Validates the patch and returns a copy of `intent` with it applied.
+    pub fn apply(&self, intent: &StyleIntent) -> Result<StyleIntent, PatchError> {
+        if let Some(et_al) = self.author_format.as_ref().and_then(|f| f.et_al.as_ref()) {
+            if et_al.use_first == 0 || et_al.use_first > et_al.min {
+                return Err(PatchError::InvalidEtAl { min: et_al.min, use_first: et_al.use_first });
+            }
+        }
+        if let Some(preset) = &self.citation_preset {
+            if !CITATION_PRESETS.contains(&preset.as_str()) {
+                return Err(PatchError::UnknownPreset { field: "citation_preset", value: preset.clone() });
+            }
+        }
+        if let Some(preset) = &self.bibliography_preset {
+            if !BIBLIOGRAPHY_PRESETS.contains(&preset.as_str()) {
+                return Err(PatchError::UnknownPreset { field: "bibliography_preset", value: preset.clone() });
+            }
+        }
+
+        Ok(StyleIntent {
+            base_archetype: self.base_archetype.clone().or_else(|| intent.base_archetype.clone()),
+            field: self.field.clone().or_else(|| intent.field.clone()),
+            class: self.class.clone().or_else(|| intent.class.clone()),
+            author_format: self.author_format.clone().or_else(|| intent.author_format.clone()),
+            has_bibliography: self.has_bibliography.or(intent.has_bibliography),
+            citation_preset: self.citation_preset.clone().or_else(|| intent.citation_preset.clone()),
+            bibliography_preset: self.bibliography_preset.clone().or_else(|| intent.bibliography_preset.clone()),
+            detailed_config: self.detailed_config.or(intent.detailed_config),
+            bibliography_sort: self.bibliography_sort.or(intent.bibliography_sort),
+            bibliography_grouping: self.bibliography_grouping.or(intent.bibliography_grouping),
+            subsequent_author_substitute: self.subsequent_author_substitute.clone().or_else(|| intent.subsequent_author_substitute.clone()),
+        })
+    }
+}
+
+/// Reasons a `StyleIntentPatch` can be rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// `use_first` must be between 1 and `min`.
+    InvalidEtAl { min: u8, use_first: u8 },
+    /// A preset name the engine doesn't know how to render.
+    UnknownPreset { field: &'static str, value: String },
+}
+
+impl std::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchError::InvalidEtAl { min, use_first } => {
+                write!(f, "invalid et al. rule: use_first {} must be between 1 and min {}", use_first, min)
+            },
+            PatchError::UnknownPreset { field, value } => write!(f, "unknown {} '{}'", field, value),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -468,11 +854,27 @@ mod tests {
         out.push_str(";\n\n");
         out.push_str(&ts::export::<EtAlConfig>(&config).unwrap());
         out.push_str(";\n\n");
+        out.push_str(&ts::export::<BibliographySort>(&config).unwrap());
+        out.push_str(";\n\n");
+        out.push_str(&ts::export::<BibliographyGrouping>(&config).unwrap());
+        out.push_str(";\n\n");
         out.push_str(&ts::export::<DecisionPackage>(&config).unwrap());
         out.push_str(";\n\n");
+        out.push_str(&ts::export::<csl::CslExport>(&config).unwrap());
+        out.push_str(";\n\n");
+        out.push_str(&ts::export::<PreviewFragment>(&config).unwrap());
+        out.push_str(";\n\n");
+        out.push_str(&ts::export::<PreviewContext>(&config).unwrap());
+        out.push_str(";\n\n");
+        out.push_str(&ts::export::<NotePreview>(&config).unwrap());
+        out.push_str(";\n\n");
+        out.push_str(&ts::export::<NotePosition>(&config).unwrap());
+        out.push_str(";\n\n");
         out.push_str(&ts::export::<Question>(&config).unwrap());
         out.push_str(";\n\n");
         out.push_str(&ts::export::<Preview>(&config).unwrap());
+        out.push_str(";\n\n");
+        out.push_str(&ts::export::<StyleIntentPatch>(&config).unwrap());
         out.push_str(";\n");
 
         std::fs::create_dir_all(path.parent().unwrap()).unwrap();
@@ -0,0 +1,21 @@
+-- Snapshot the CSLN and the author of each change, and number versions per style
+ALTER TABLE history ADD COLUMN csln_snapshot TEXT;
+ALTER TABLE history ADD COLUMN author_id UUID REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE history ADD COLUMN version INTEGER;
+
+-- Existing snapshots were all written by the style's owner
+UPDATE history h
+SET author_id = s.user_id
+FROM styles s
+WHERE h.style_id = s.id;
+
+UPDATE history h
+SET version = numbered.n
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY style_id ORDER BY created_at, id) AS n
+    FROM history
+) numbered
+WHERE h.id = numbered.id;
+
+ALTER TABLE history ALTER COLUMN version SET NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS history_style_version ON history (style_id, version);
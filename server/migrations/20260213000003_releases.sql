@@ -0,0 +1,31 @@
+-- Published, immutable versions of a style
+CREATE TABLE IF NOT EXISTS releases (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    style_id UUID NOT NULL REFERENCES styles(id) ON DELETE CASCADE,
+    version TEXT NOT NULL,
+    major INTEGER NOT NULL,
+    minor INTEGER NOT NULL,
+    patch INTEGER NOT NULL,
+    changelog TEXT NOT NULL DEFAULT '',
+    title TEXT NOT NULL,
+    intent JSONB NOT NULL,
+    csln TEXT,
+    published_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (style_id, version)
+);
+
+CREATE INDEX IF NOT EXISTS releases_latest ON releases (style_id, major DESC, minor DESC, patch DESC);
+
+-- Releases never change once published
+CREATE OR REPLACE FUNCTION prevent_release_update()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'published releases are immutable';
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER releases_immutable
+BEFORE UPDATE ON releases
+FOR EACH ROW
+EXECUTE PROCEDURE prevent_release_update();
@@ -0,0 +1,4 @@
+-- Which side of a style is authoritative: 'intent' when the CSLN is
+-- generated from the intent, 'csln' when it was edited by hand
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS csln_source TEXT NOT NULL DEFAULT 'intent'
+    CHECK (csln_source IN ('intent', 'csln'));
@@ -0,0 +1,15 @@
+-- A counter bumped by every update, used as the style's ETag
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS revision INTEGER NOT NULL DEFAULT 1;
+
+CREATE OR REPLACE FUNCTION bump_revision()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.revision = OLD.revision + 1;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER bump_styles_revision
+BEFORE UPDATE ON styles
+FOR EACH ROW
+EXECUTE PROCEDURE bump_revision();
@@ -0,0 +1,4 @@
+-- Deleted styles stay in the owner's trash for 30 days before being purged
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS styles_deleted_at ON styles (deleted_at) WHERE deleted_at IS NOT NULL;
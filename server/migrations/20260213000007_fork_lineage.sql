@@ -0,0 +1,6 @@
+-- The style a fork was copied from, and the parent's history version at
+-- the time of forking (or of the last upstream merge)
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS forked_from UUID REFERENCES styles(id) ON DELETE SET NULL;
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS forked_from_version INTEGER;
+
+CREATE INDEX IF NOT EXISTS styles_forked_from ON styles (forked_from) WHERE forked_from IS NOT NULL;
@@ -0,0 +1,6 @@
+-- Overlay forks store only their differences from the pinned parent version
+-- (forked_from, forked_from_version) as merge patches:
+-- {"intent": {...}, "csln": {...}}. NULL for forks that are full copies.
+-- intent and csln keep the resolved result, which stands in for the parent
+-- if it is ever purged.
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS overrides JSONB;
@@ -0,0 +1,10 @@
+-- Full-text search over public styles, by title and discipline
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS search_vector TSVECTOR
+    GENERATED ALWAYS AS (
+        to_tsvector('simple', COALESCE(title, '') || ' ' || COALESCE(intent->>'field', ''))
+    ) STORED;
+
+CREATE INDEX IF NOT EXISTS styles_search ON styles USING GIN (search_vector);
+
+-- Popularity counts bookmarks and forks per style
+CREATE INDEX IF NOT EXISTS bookmarks_style ON bookmarks (style_id);
@@ -0,0 +1,24 @@
+-- Descriptive metadata, shown and filtered on in the hub
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS description TEXT NOT NULL DEFAULT '';
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS disciplines TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS tags TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS license TEXT;
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS locale TEXT;
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS citation_class TEXT;
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS documentation_url TEXT;
+
+UPDATE styles SET
+    citation_class = intent->>'class',
+    disciplines = CASE WHEN intent->>'field' IS NULL THEN '{}' ELSE ARRAY[intent->>'field'] END;
+
+CREATE INDEX IF NOT EXISTS styles_disciplines ON styles USING GIN (disciplines);
+CREATE INDEX IF NOT EXISTS styles_tags ON styles USING GIN (tags);
+
+-- Search descriptions too
+ALTER TABLE styles DROP COLUMN IF EXISTS search_vector;
+ALTER TABLE styles ADD COLUMN search_vector TSVECTOR
+    GENERATED ALWAYS AS (
+        to_tsvector('simple', COALESCE(title, '') || ' ' || description || ' ' || COALESCE(intent->>'field', ''))
+    ) STORED;
+
+CREATE INDEX IF NOT EXISTS styles_search ON styles USING GIN (search_vector);
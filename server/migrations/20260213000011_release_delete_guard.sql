@@ -0,0 +1,12 @@
+-- Releases can't be deleted either, directly or by deleting their style
+CREATE OR REPLACE FUNCTION prevent_release_delete()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'published releases cannot be deleted';
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER releases_undeletable
+BEFORE DELETE ON releases
+FOR EACH ROW
+EXECUTE PROCEDURE prevent_release_delete();
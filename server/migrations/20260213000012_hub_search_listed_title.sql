@@ -0,0 +1,4 @@
+-- Hub search matches the title it lists, which may be a release's, so it
+-- can't be precomputed from the draft
+DROP INDEX IF EXISTS styles_search;
+ALTER TABLE styles DROP COLUMN IF EXISTS search_vector;
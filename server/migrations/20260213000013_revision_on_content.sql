@@ -0,0 +1,14 @@
+-- Only content changes bump the revision. Metadata is refreshed in a second
+-- update after each save, which used to bump it again and leave the ETag
+-- returned by the save stale.
+DROP TRIGGER IF EXISTS bump_styles_revision ON styles;
+
+CREATE TRIGGER bump_styles_revision
+BEFORE UPDATE ON styles
+FOR EACH ROW
+WHEN (
+    (OLD.title, OLD.intent, OLD.csln, OLD.csln_source, OLD.is_public, OLD.forked_from, OLD.forked_from_version, OLD.overrides)
+    IS DISTINCT FROM
+    (NEW.title, NEW.intent, NEW.csln, NEW.csln_source, NEW.is_public, NEW.forked_from, NEW.forked_from_version, NEW.overrides)
+)
+EXECUTE PROCEDURE bump_revision();
@@ -0,0 +1,52 @@
+-- Hub search is indexed again. The vector covers the title the hub lists,
+-- the latest release's when there is one, so it's kept up to date by
+-- triggers on both styles and releases rather than generated.
+ALTER TABLE styles ADD COLUMN IF NOT EXISTS search_vector TSVECTOR;
+
+CREATE OR REPLACE FUNCTION style_search_vector(style_id UUID, title TEXT, description TEXT, disciplines TEXT[])
+RETURNS TSVECTOR AS $$
+    SELECT to_tsvector('simple',
+        COALESCE(
+            (SELECT r.title FROM releases r WHERE r.style_id = $1 ORDER BY r.major DESC, r.minor DESC, r.patch DESC LIMIT 1),
+            $2,
+            ''
+        ) || ' ' || $3 || ' ' || array_to_string($4, ' ')
+    );
+$$ language 'sql' STABLE;
+
+CREATE OR REPLACE FUNCTION refresh_style_search_vector()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.search_vector = style_search_vector(NEW.id, NEW.title, NEW.description, NEW.disciplines);
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER styles_search_vector
+BEFORE INSERT OR UPDATE OF title, description, disciplines ON styles
+FOR EACH ROW
+EXECUTE PROCEDURE refresh_style_search_vector();
+
+-- Releases are immutable and undeletable, so only a new one changes the
+-- listed title
+CREATE OR REPLACE FUNCTION refresh_released_style_search_vector()
+RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE styles
+    SET search_vector = style_search_vector(id, title, description, disciplines)
+    WHERE id = NEW.style_id;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER releases_search_vector
+AFTER INSERT ON releases
+FOR EACH ROW
+EXECUTE PROCEDURE refresh_released_style_search_vector();
+
+-- Filling the column in isn't an edit
+ALTER TABLE styles DISABLE TRIGGER update_styles_updated_at;
+UPDATE styles SET search_vector = style_search_vector(id, title, description, disciplines);
+ALTER TABLE styles ENABLE TRIGGER update_styles_updated_at;
+
+CREATE INDEX IF NOT EXISTS styles_search ON styles USING GIN (search_vector);
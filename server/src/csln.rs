@@ -0,0 +1,88 @@
+use csln_core::Style;
+use intent_engine::StyleIntent;
+use serde_json::Value;
+
+/// The intent is authoritative unless the CSLN was edited by hand.
+pub const SOURCE_INTENT: &str = "intent";
+pub const SOURCE_CSLN: &str = "csln";
+
+/// The CSLN to store for a style, and which side it came from.
+pub struct Reconciled {
+    pub csln: String,
+    pub source: &'static str,
+}
+
+/// Makes the stored CSLN agree with the intent. Without CSLN, or with CSLN
+/// identical to what the intent generates, the generated document is stored.
+/// Anything else is kept as a hand edit, provided it parses as a `Style`.
+pub fn reconcile(intent: &Value, csln: Option<&str>) -> Result<Reconciled, String> {
+    let intent: StyleIntent = serde_json::from_value(intent.clone())
+        .map_err(|e| format!("Invalid intent: {}", e))?;
+    let generated = serde_yaml::to_string(&intent.to_style())
+        .map_err(|e| format!("Failed to serialize style: {}", e))?;
+
+    let csln = match csln.filter(|c| !c.trim().is_empty()) {
+        Some(csln) => csln,
+        None => return Ok(Reconciled { csln: generated, source: SOURCE_INTENT }),
+    };
+
+    let style: Style = serde_yaml::from_str(csln).map_err(|e| format!("Invalid CSLN: {}", e))?;
+    // Formatting differences alone don't make a hand edit
+    let normalized = serde_yaml::to_string(&style).map_err(|e| format!("Failed to serialize style: {}", e))?;
+    if normalized == generated {
+        Ok(Reconciled { csln: generated, source: SOURCE_INTENT })
+    } else {
+        Ok(Reconciled { csln: csln.to_string(), source: SOURCE_CSLN })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn intent() -> Value {
+        json!({"class": "author_date", "has_bibliography": true})
+    }
+
+    fn generated() -> Style {
+        let intent: StyleIntent = serde_json::from_value(intent()).unwrap();
+        intent.to_style()
+    }
+
+    #[test]
+    fn without_csln_the_generated_style_is_stored() {
+        for csln in [None, Some(""), Some("  \n")] {
+            let reconciled = reconcile(&intent(), csln).unwrap();
+            assert_eq!(reconciled.source, SOURCE_INTENT);
+            assert_eq!(reconciled.csln, serde_yaml::to_string(&generated()).unwrap());
+        }
+    }
+
+    #[test]
+    fn generated_csln_in_another_layout_is_not_a_hand_edit() {
+        let reformatted = format!("# generated\n\n{}\n\n", serde_yaml::to_string(&generated()).unwrap());
+        let reconciled = reconcile(&intent(), Some(&reformatted)).unwrap();
+        assert_eq!(reconciled.source, SOURCE_INTENT);
+        assert_eq!(reconciled.csln, serde_yaml::to_string(&generated()).unwrap());
+    }
+
+    #[test]
+    fn hand_edits_are_kept_verbatim() {
+        let mut style = generated();
+        style.info.title = Some("My House Style".to_string());
+        let edited = format!("# edited by hand\n{}", serde_yaml::to_string(&style).unwrap());
+        let reconciled = reconcile(&intent(), Some(&edited)).unwrap();
+        assert_eq!(reconciled.source, SOURCE_CSLN);
+        assert_eq!(reconciled.csln, edited);
+    }
+
+    #[test]
+    fn invalid_input_is_rejected() {
+        let err = reconcile(&intent(), Some("info: [unclosed")).err().unwrap();
+        assert!(err.starts_with("Invalid CSLN"), "{}", err);
+
+        let err = reconcile(&json!({"class": "sideways"}), None).err().unwrap();
+        assert!(err.starts_with("Invalid intent"), "{}", err);
+    }
+}
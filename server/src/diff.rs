@@ -0,0 +1,430 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// A value that differs between two JSON documents, addressed by a dotted
+/// path such as `author_format.et_al.min`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub path: String,
+    pub from: Value,
+    pub to: Value,
+}
+
+/// Lists the leaf values that differ between `from` and `to`. Objects are
+/// compared key by key; arrays and scalars are compared whole.
+pub fn json_changes(from: &Value, to: &Value) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    collect_changes("", from, to, &mut changes);
+    changes
+}
+
+fn collect_changes(path: &str, from: &Value, to: &Value, changes: &mut Vec<FieldChange>) {
+    match (from, to) {
+        (Value::Object(a), Value::Object(b)) => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys().filter(|k| !a.contains_key(*k))).collect();
+            keys.sort();
+            for key in keys {
+                let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                collect_changes(
+                    &child,
+                    a.get(key).unwrap_or(&Value::Null),
+                    b.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        },
+        _ if from != to => changes.push(FieldChange {
+            path: path.to_string(),
+            from: from.clone(),
+            to: to.clone(),
+        }),
+        _ => {},
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineOp {
+    Equal,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LineChange {
+    pub op: LineOp,
+    pub text: String,
+}
+
+/// Largest LCS table `line_diff` builds, in cells. Past it the differing
+/// middle of the texts is reported as removed then added wholesale.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// A line diff of two texts, in order, via the longest common subsequence.
+/// Common leading and trailing lines are matched up front, so the quadratic
+/// table only covers the part that changed.
+pub fn line_diff(from: &str, to: &str) -> Vec<LineChange> {
+    let a: Vec<&str> = from.lines().collect();
+    let b: Vec<&str> = to.lines().collect();
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+
+    let mut lines: Vec<LineChange> = a[..prefix].iter().map(|text| line(LineOp::Equal, text)).collect();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    if (a_mid.len() + 1).saturating_mul(b_mid.len() + 1) > MAX_DIFF_CELLS {
+        lines.extend(a_mid.iter().map(|text| line(LineOp::Removed, text)));
+        lines.extend(b_mid.iter().map(|text| line(LineOp::Added, text)));
+    } else {
+        lcs_diff(a_mid, b_mid, &mut lines);
+    }
+    lines.extend(a[a.len() - suffix..].iter().map(|text| line(LineOp::Equal, text)));
+    lines
+}
+
+fn line(op: LineOp, text: &str) -> LineChange {
+    LineChange { op, text: text.to_string() }
+}
+
+fn lcs_diff(a: &[&str], b: &[&str], lines: &mut Vec<LineChange>) {
+    // lcs[i][j] is the LCS length of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let change = if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+            line(LineOp::Equal, a[i - 1])
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            j += 1;
+            line(LineOp::Added, b[j - 1])
+        } else {
+            i += 1;
+            line(LineOp::Removed, a[i - 1])
+        };
+        lines.push(change);
+    }
+}
+
+/// A value changed differently on both sides of a three-way merge.
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeConflict {
+    pub path: String,
+    pub base: Value,
+    pub ours: Value,
+    pub theirs: Value,
+}
+
+/// Merges the changes `theirs` made since `base` into `ours`. Objects are
+/// merged key by key; anything else changed on both sides is a conflict,
+/// resolved in favour of `ours` and reported.
+pub fn merge3(base: &Value, ours: &Value, theirs: &Value) -> (Value, Vec<MergeConflict>) {
+    let mut conflicts = Vec::new();
+    let merged = merge_values("", Some(base), Some(ours), Some(theirs), &mut conflicts);
+    (merged.unwrap_or(Value::Null), conflicts)
+}
+
+/// Merges one value; `None` is a key missing from that side, so a key
+/// removed on one side and untouched on the other stays removed.
+fn merge_values(
+    path: &str,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<MergeConflict>,
+) -> Option<Value> {
+    if ours == theirs || base == theirs {
+        return ours.cloned();
+    }
+    if base == ours {
+        return theirs.cloned();
+    }
+    match (ours, theirs) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            let base = base.and_then(Value::as_object);
+            let mut merged = serde_json::Map::new();
+            for key in a.keys().chain(b.keys().filter(|k| !a.contains_key(*k))) {
+                let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                let value = merge_values(&child, base.and_then(|base| base.get(key)), a.get(key), b.get(key), conflicts);
+                if let Some(value) = value {
+                    merged.insert(key.clone(), value);
+                }
+            }
+            Some(Value::Object(merged))
+        },
+        _ => {
+            let or_null = |value: Option<&Value>| value.cloned().unwrap_or(Value::Null);
+            conflicts.push(MergeConflict {
+                path: path.to_string(),
+                base: or_null(base),
+                ours: or_null(ours),
+                theirs: or_null(theirs),
+            });
+            ours.cloned()
+        },
+    }
+}
+
+/// Applies a JSON merge patch (RFC 7396): objects merge key by key, `null`
+/// removes a key, and anything else replaces the target.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => {
+            *target = patch.clone();
+            return;
+        },
+    };
+    if !target.is_object() {
+        *target = Value::Object(serde_json::Map::new());
+    }
+    let target = target.as_object_mut().expect("target is an object");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// The merge patch that turns `from` into `to`; an empty object when they
+/// are equal. Merge patches can't set a value to `null`, so a `null` in
+/// `to` comes out of `merge_patch` as a missing key.
+pub fn make_patch(from: &Value, to: &Value) -> Value {
+    match (from, to) {
+        (Value::Object(a), Value::Object(b)) => {
+            let mut patch = serde_json::Map::new();
+            for key in a.keys().filter(|k| !b.contains_key(*k)) {
+                patch.insert(key.clone(), Value::Null);
+            }
+            for (key, value) in b {
+                match a.get(key) {
+                    Some(old) if old == value => {},
+                    Some(old) if old.is_object() && value.is_object() => {
+                        patch.insert(key.clone(), make_patch(old, value));
+                    },
+                    _ => {
+                        patch.insert(key.clone(), value.clone());
+                    },
+                }
+            }
+            Value::Object(patch)
+        },
+        _ if from == to => Value::Object(serde_json::Map::new()),
+        _ => to.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(changes: &[FieldChange]) -> Vec<&str> {
+        changes.iter().map(|c| c.path.as_str()).collect()
+    }
+
+    fn ops(lines: &[LineChange]) -> Vec<(LineOp, &str)> {
+        lines.iter().map(|l| (l.op, l.text.as_str())).collect()
+    }
+
+    #[test]
+    fn equal_documents_have_no_changes() {
+        let doc = json!({"class": "numeric", "author_format": {"form": "long"}});
+        assert!(json_changes(&doc, &doc).is_empty());
+    }
+
+    #[test]
+    fn changes_are_addressed_by_dotted_path_in_key_order() {
+        let from = json!({"class": "numeric", "author_format": {"form": "long", "et_al": {"min": 3}}});
+        let to = json!({"class": "author_date", "author_format": {"form": "long", "et_al": {"min": 4}}});
+        let changes = json_changes(&from, &to);
+        assert_eq!(paths(&changes), ["author_format.et_al.min", "class"]);
+        assert_eq!(changes[0].from, json!(3));
+        assert_eq!(changes[0].to, json!(4));
+    }
+
+    #[test]
+    fn added_and_removed_keys_change_from_or_to_null() {
+        let changes = json_changes(&json!({"field": "humanities"}), &json!({"class": "footnote"}));
+        assert_eq!(paths(&changes), ["class", "field"]);
+        assert_eq!((&changes[0].from, &changes[0].to), (&Value::Null, &json!("footnote")));
+        assert_eq!((&changes[1].from, &changes[1].to), (&json!("humanities"), &Value::Null));
+    }
+
+    #[test]
+    fn arrays_and_type_changes_are_compared_whole() {
+        let changes = json_changes(&json!({"keys": [1, 2], "et_al": {"min": 3}}), &json!({"keys": [2, 1], "et_al": null}));
+        assert_eq!(paths(&changes), ["et_al", "keys"]);
+        assert_eq!(changes[0].from, json!({"min": 3}));
+
+        let changes = json_changes(&json!(1), &json!("1"));
+        assert_eq!(paths(&changes), [""]);
+    }
+
+    #[test]
+    fn merge3_takes_changes_from_either_side() {
+        let base = json!({"class": "numeric", "field": "sciences", "sort": "author"});
+        let ours = json!({"class": "numeric", "field": "humanities", "sort": "author"});
+        let theirs = json!({"class": "author_date", "field": "sciences", "sort": "author", "wrap": "brackets"});
+        let (merged, conflicts) = merge3(&base, &ours, &theirs);
+        assert_eq!(merged, json!({"class": "author_date", "field": "humanities", "sort": "author", "wrap": "brackets"}));
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn merge3_keeps_ours_on_conflict_and_reports_it() {
+        let base = json!({"class": "numeric", "field": "sciences"});
+        let ours = json!({"class": "footnote", "field": "sciences"});
+        let theirs = json!({"class": "author_date", "field": "sciences"});
+        let (merged, conflicts) = merge3(&base, &ours, &theirs);
+        assert_eq!(merged, ours);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, "class");
+        assert_eq!((&conflicts[0].base, &conflicts[0].ours, &conflicts[0].theirs),
+            (&json!("numeric"), &json!("footnote"), &json!("author_date")));
+    }
+
+    #[test]
+    fn merge3_keeps_deletions_from_either_side() {
+        let base = json!({"class": "numeric", "field": "sciences", "sort": "author"});
+        let ours = json!({"class": "numeric", "sort": "author"});
+        let theirs = json!({"class": "numeric", "field": "sciences"});
+        let (merged, conflicts) = merge3(&base, &ours, &theirs);
+        assert_eq!(merged, json!({"class": "numeric"}));
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn merge3_deleting_a_key_the_other_side_changed_conflicts() {
+        let base = json!({"field": "sciences"});
+        let (merged, conflicts) = merge3(&base, &json!({}), &json!({"field": "humanities"}));
+        assert_eq!(merged, json!({}));
+        assert_eq!(conflicts[0].path, "field");
+        assert_eq!(conflicts[0].ours, Value::Null);
+    }
+
+    #[test]
+    fn merge3_recurses_into_nested_objects() {
+        let base = json!({"author_format": {"form": "long", "et_al": {"min": 3, "use_first": 1}}});
+        let ours = json!({"author_format": {"form": "short", "et_al": {"min": 3, "use_first": 1}}});
+        let theirs = json!({"author_format": {"form": "long", "et_al": {"min": 4}}});
+        let (merged, conflicts) = merge3(&base, &ours, &theirs);
+        assert_eq!(merged, json!({"author_format": {"form": "short", "et_al": {"min": 4}}}));
+        assert!(conflicts.is_empty());
+
+        let theirs = json!({"author_format": {"form": "long", "et_al": {"min": 3, "use_first": 2}}});
+        let ours = json!({"author_format": {"form": "long", "et_al": {"min": 3, "use_first": 3}}});
+        let (_, conflicts) = merge3(&base, &ours, &theirs);
+        assert_eq!(conflicts[0].path, "author_format.et_al.use_first");
+    }
+
+    #[test]
+    fn merge3_keeps_explicit_nulls() {
+        let base = json!({"field": "sciences", "sort": null});
+        let ours = json!({"field": null, "sort": null});
+        let (merged, conflicts) = merge3(&base, &ours, &base);
+        assert_eq!(merged, ours);
+        assert!(conflicts.is_empty());
+    }
+
+    fn round_trip(parent: &Value, x: &Value) -> Value {
+        let mut patched = parent.clone();
+        merge_patch(&mut patched, &make_patch(parent, x));
+        patched
+    }
+
+    #[test]
+    fn patches_round_trip() {
+        let parent = json!({
+            "class": "numeric",
+            "field": null,
+            "author_format": {"form": "long", "et_al": {"min": 3, "use_first": 1}},
+            "keys": ["author", "date"],
+        });
+        let cases = [
+            parent.clone(),
+            json!({}),
+            // Changed, added and removed keys, nested
+            json!({
+                "class": "author_date",
+                "field": null,
+                "author_format": {"form": "long", "et_al": {"min": 4}, "initials": true},
+                "keys": ["date"],
+            }),
+            // Objects replacing scalars and the reverse
+            json!({"class": {"name": "numeric"}, "field": null, "author_format": "short", "keys": []}),
+        ];
+        for x in cases {
+            assert_eq!(round_trip(&parent, &x), x);
+        }
+    }
+
+    #[test]
+    fn removals_patch_as_null() {
+        let parent = json!({"class": "numeric", "author_format": {"form": "long", "et_al": {"min": 3}}});
+        let x = json!({"author_format": {"form": "long"}});
+        assert_eq!(make_patch(&parent, &x), json!({"class": null, "author_format": {"et_al": null}}));
+        assert_eq!(round_trip(&parent, &x), x);
+    }
+
+    #[test]
+    fn equal_documents_patch_as_empty() {
+        let doc = json!({"class": "numeric", "field": null});
+        assert_eq!(make_patch(&doc, &doc), json!({}));
+        assert_eq!(make_patch(&json!(3), &json!(3)), json!({}));
+        assert_eq!(round_trip(&json!(3), &json!("three")), json!("three"));
+    }
+
+    #[test]
+    fn nulls_in_the_target_become_missing_keys() {
+        let parent = json!({"class": "numeric", "author_format": {"form": "long"}});
+        let x = json!({"class": null, "author_format": {"form": "long", "et_al": null}});
+        assert_eq!(round_trip(&parent, &x), json!({"author_format": {"form": "long"}}));
+    }
+
+    #[test]
+    fn line_diff_keeps_order() {
+        let lines = line_diff("a\nb\nc\n", "a\nx\nc\nd\n");
+        assert_eq!(ops(&lines), [
+            (LineOp::Equal, "a"),
+            (LineOp::Added, "x"),
+            (LineOp::Removed, "b"),
+            (LineOp::Equal, "c"),
+            (LineOp::Added, "d"),
+        ]);
+    }
+
+    #[test]
+    fn line_diff_of_empty_texts() {
+        assert!(line_diff("", "").is_empty());
+        assert_eq!(ops(&line_diff("", "a\nb")), [(LineOp::Added, "a"), (LineOp::Added, "b")]);
+        assert_eq!(ops(&line_diff("a\nb", "")), [(LineOp::Removed, "a"), (LineOp::Removed, "b")]);
+    }
+
+    #[test]
+    fn line_diff_keeps_the_longest_common_run() {
+        let lines = line_diff("x\na\nb\nc", "a\nb\nc\nx");
+        let equal: Vec<&str> = lines.iter().filter(|l| l.op == LineOp::Equal).map(|l| l.text.as_str()).collect();
+        assert_eq!(equal, ["a", "b", "c"]);
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn line_diff_of_large_texts_stays_bounded() {
+        let from: String = (0..5000).map(|i| format!("from {}\n", i)).collect();
+        let to: String = (0..5000).map(|i| format!("to {}\n", i)).collect();
+        let lines = line_diff(&format!("head\n{}tail", from), &format!("head\n{}tail", to));
+        assert_eq!(lines.len(), 10_002);
+        assert_eq!((lines[0].op, lines[0].text.as_str()), (LineOp::Equal, "head"));
+        assert_eq!((lines[1].op, lines[1].text.as_str()), (LineOp::Removed, "from 0"));
+        assert_eq!((lines[5001].op, lines[5001].text.as_str()), (LineOp::Added, "to 0"));
+        assert_eq!((lines[10_001].op, lines[10_001].text.as_str()), (LineOp::Equal, "tail"));
+    }
+}
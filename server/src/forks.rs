@@ -0,0 +1,347 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{StatusCode, Uri},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::csln::{self, SOURCE_CSLN};
+use crate::diff::{self, FieldChange, MergeConflict};
+use crate::history;
+use crate::metadata::{self, MetadataPatch};
+use crate::overlay;
+use crate::pagination::{Cursor, Page, PageParams};
+use crate::{auth, AppState, StyleRow};
+
+/// The parent's latest history version, which identifies its current state.
+async fn latest_version(conn: &mut sqlx::PgConnection, style_id: Uuid) -> Option<i32> {
+    sqlx::query_scalar!("SELECT MAX(version) FROM history WHERE style_id = $1", style_id)
+        .fetch_one(conn)
+        .await
+        .expect("Failed to fetch history")
+}
+
+/// The parent's intent at the version a fork was based on. Forks whose base
+/// isn't recorded merge against an empty intent, so every field both sides
+/// set differently is reported as a conflict.
+async fn base_intent(conn: &mut sqlx::PgConnection, style_id: Uuid, version: Option<i32>) -> Value {
+    let snapshot = match version {
+        Some(version) => sqlx::query_scalar!(
+            "SELECT intent_snapshot FROM history WHERE style_id = $1 AND version = $2",
+            style_id,
+            version
+        )
+        .fetch_optional(conn)
+        .await
+        .expect("Failed to fetch history"),
+        None => None,
+    };
+    snapshot.unwrap_or_else(|| json!({}))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForkMode {
+    /// An independent copy of the parent.
+    #[default]
+    Copy,
+    /// Only the differences from a pinned parent version are stored, and
+    /// the fork is resolved against that version when read.
+    Overlay,
+}
+
+#[derive(Deserialize)]
+pub struct ForkRequest {
+    #[serde(default)]
+    mode: ForkMode,
+}
+
+pub async fn fork_style(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path(id): Path<Uuid>,
+    payload: Option<Json<ForkRequest>>,
+) -> impl IntoResponse {
+    let mode = payload.map(|Json(p)| p.mode).unwrap_or_default();
+    let mut tx = state.db.begin().await.expect("Failed to start transaction");
+
+    // 1. Fetch the original style (must be public or owned by user)
+    let original = sqlx::query!(
+        "SELECT title, intent, csln, csln_source FROM styles WHERE id = $1 AND (is_public = true OR user_id = $2) AND deleted_at IS NULL",
+        id,
+        user.id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("Database error");
+
+    let original = match original {
+        Some(s) => s,
+        None => return (StatusCode::NOT_FOUND, "Source style not found").into_response(),
+    };
+    let parent_version = latest_version(&mut tx, id).await;
+    let overrides = match (mode, parent_version) {
+        (ForkMode::Copy, _) => None,
+        (ForkMode::Overlay, Some(_)) => Some(json!({ "intent": {}, "csln": {} })),
+        (ForkMode::Overlay, None) => {
+            return (StatusCode::UNPROCESSABLE_ENTITY, "Source style has no recorded version to pin").into_response()
+        },
+    };
+
+    // 2. Create the copy, remembering where it came from
+    let forked_title = format!("{} (Fork)", original.title);
+    let style = sqlx::query_as!(
+        StyleRow,
+        r#"
+        INSERT INTO styles (user_id, title, intent, csln, csln_source, is_public, forked_from, forked_from_version, overrides)
+        VALUES ($1, $2, $3, $4, $5, false, $6, $7, $8)
+        RETURNING id, user_id, title, intent, csln, csln_source, is_public, revision, created_at, updated_at
+        "#,
+        user.id,
+        forked_title,
+        original.intent,
+        original.csln,
+        original.csln_source,
+        id,
+        parent_version,
+        overrides
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .expect("Failed to fork style");
+
+    if let Err(e) = history::record(&mut tx, &style, user.id).await {
+        println!("Failed to record history for {}: {}", style.id, e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to record history").into_response();
+    }
+    if let Err(e) = metadata::copy(&mut tx, id, style.id).await {
+        println!("Failed to copy metadata to {}: {}", style.id, e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update metadata").into_response();
+    }
+    if let Err(e) = metadata::refresh(&mut tx, &style, &MetadataPatch::default()).await {
+        println!("Failed to refresh metadata for {}: {}", style.id, e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update metadata").into_response();
+    }
+    tx.commit().await.expect("Failed to commit fork");
+
+    Json(style).into_response()
+}
+
+#[derive(Serialize)]
+pub struct ForkSummary {
+    id: Uuid,
+    user_id: Uuid,
+    title: String,
+    forked_from_version: Option<i32>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Lists the forks of a style that the user can see.
+pub async fn list_forks(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path(id): Path<Uuid>,
+    uri: Uri,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let after = match page.after() {
+        Ok(after) => after,
+        Err(()) => return (StatusCode::BAD_REQUEST, "Invalid cursor").into_response(),
+    };
+
+    let forks = sqlx::query_as!(
+        ForkSummary,
+        r#"
+        SELECT id, user_id, title, forked_from_version, updated_at
+        FROM styles
+        WHERE forked_from = $1 AND (user_id = $2 OR is_public = true) AND deleted_at IS NULL
+          AND ($3::BIGINT IS NULL OR ((EXTRACT(EPOCH FROM updated_at) * 1000000)::BIGINT, id) < ($3, $4::UUID))
+        ORDER BY updated_at DESC, id DESC
+        LIMIT $5
+        "#,
+        id,
+        user.id,
+        after.map(|c| c.key),
+        after.map(|c| c.id),
+        page.fetch_limit()
+    )
+    .fetch_all(&state.db)
+    .await
+    .expect("Failed to fetch forks");
+
+    Json(Page::new(forks, &page, &uri, |f| Cursor::at_time(f.updated_at, f.id))).into_response()
+}
+
+struct Lineage {
+    parent_id: Uuid,
+    parent_title: String,
+    parent_intent: Value,
+    forked_from_version: Option<i32>,
+    latest_version: Option<i32>,
+    overrides: Option<Value>,
+}
+
+/// Looks up a fork's parent, provided the parent is still visible to the user.
+async fn lineage(conn: &mut sqlx::PgConnection, id: Uuid, user_id: Uuid) -> Result<Lineage, (StatusCode, &'static str)> {
+    let fork = sqlx::query!(
+        "SELECT forked_from, forked_from_version, overrides FROM styles WHERE id = $1 AND (user_id = $2 OR is_public = true) AND deleted_at IS NULL",
+        id,
+        user_id
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .expect("Failed to fetch style")
+    .ok_or((StatusCode::NOT_FOUND, "Style not found"))?;
+
+    let parent_id = fork.forked_from.ok_or((StatusCode::NOT_FOUND, "Style is not a fork"))?;
+    let parent = sqlx::query!(
+        "SELECT title, intent FROM styles WHERE id = $1 AND (user_id = $2 OR is_public = true) AND deleted_at IS NULL",
+        parent_id,
+        user_id
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .expect("Failed to fetch style")
+    .ok_or((StatusCode::NOT_FOUND, "Upstream style is no longer available"))?;
+
+    Ok(Lineage {
+        parent_id,
+        parent_title: parent.title,
+        parent_intent: parent.intent,
+        forked_from_version: fork.forked_from_version,
+        latest_version: latest_version(conn, parent_id).await,
+        overrides: fork.overrides,
+    })
+}
+
+#[derive(Serialize)]
+pub struct UpstreamStatus {
+    parent_id: Uuid,
+    parent_title: String,
+    forked_from_version: Option<i32>,
+    latest_version: Option<i32>,
+    /// Whether the parent's intent has changed since the fork was made or
+    /// last merged.
+    changed: bool,
+    /// The parent's intent changes since then.
+    changes: Vec<FieldChange>,
+    /// For overlay forks, the merge patches applied to the parent.
+    overrides: Option<Value>,
+}
+
+pub async fn upstream_status(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let mut conn = state.db.acquire().await.expect("Failed to acquire connection");
+    let lineage = match lineage(&mut conn, id, user.id).await {
+        Ok(l) => l,
+        Err(e) => return e.into_response(),
+    };
+
+    let base = base_intent(&mut conn, lineage.parent_id, lineage.forked_from_version).await;
+    let changes = diff::json_changes(&base, &lineage.parent_intent);
+    Json(UpstreamStatus {
+        parent_id: lineage.parent_id,
+        parent_title: lineage.parent_title,
+        forked_from_version: lineage.forked_from_version,
+        latest_version: lineage.latest_version,
+        changed: !changes.is_empty(),
+        changes,
+        overrides: lineage.overrides,
+    })
+    .into_response()
+}
+
+#[derive(Serialize)]
+pub struct MergeResponse {
+    style: StyleRow,
+    /// Fields both sides changed; the fork's value was kept.
+    conflicts: Vec<MergeConflict>,
+}
+
+/// Applies the parent's intent changes since the fork's base version to the
+/// fork, then moves the base to the parent's latest version. A hand-edited
+/// CSLN is kept as-is and only the intent is merged. Overlay forks are just
+/// re-pinned, since their overrides apply on top of any version.
+pub async fn merge_upstream(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let mut tx = state.db.begin().await.expect("Failed to start transaction");
+
+    let fork = sqlx::query!(
+        "SELECT intent, csln, csln_source FROM styles WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL",
+        id,
+        user.id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("Failed to fetch style");
+
+    let fork = match fork {
+        Some(f) => f,
+        None => return (StatusCode::NOT_FOUND, "Style not found").into_response(),
+    };
+    let lineage = match lineage(&mut tx, id, user.id).await {
+        Ok(l) => l,
+        Err(e) => return e.into_response(),
+    };
+
+    let merged = match lineage.overrides {
+        Some(overrides) => {
+            let layer = overlay::Layer {
+                parent_id: lineage.parent_id,
+                version: lineage.latest_version.or(lineage.forked_from_version).unwrap_or_default(),
+                overrides,
+            };
+            overlay::resolve(&mut tx, &layer).await.map(|(intent, reconciled)| (intent, reconciled, Vec::new()))
+        },
+        None => {
+            let base = base_intent(&mut tx, lineage.parent_id, lineage.forked_from_version).await;
+            let (intent, conflicts) = diff::merge3(&base, &fork.intent, &lineage.parent_intent);
+            let hand_edited = (fork.csln_source == SOURCE_CSLN).then_some(fork.csln.as_deref()).flatten();
+            csln::reconcile(&intent, hand_edited).map(|reconciled| (intent, reconciled, conflicts))
+        },
+    };
+    let (intent, reconciled, conflicts) = match merged {
+        Ok(m) => m,
+        Err(e) => return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+    };
+
+    let style = sqlx::query_as!(
+        StyleRow,
+        r#"
+        UPDATE styles
+        SET intent = $1, csln = $2, csln_source = $3, forked_from_version = $4
+        WHERE id = $5
+        RETURNING id, user_id, title, intent, csln, csln_source, is_public, revision, created_at, updated_at
+        "#,
+        intent,
+        reconciled.csln,
+        reconciled.source,
+        lineage.latest_version,
+        id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .expect("Failed to merge upstream");
+
+    if let Err(e) = history::record(&mut tx, &style, user.id).await {
+        println!("Failed to record merge into {}: {}", id, e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to record history").into_response();
+    }
+    if let Err(e) = metadata::refresh(&mut tx, &style, &MetadataPatch::default()).await {
+        println!("Failed to refresh metadata for {}: {}", style.id, e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update metadata").into_response();
+    }
+    tx.commit().await.expect("Failed to commit merge");
+
+    Json(MergeResponse { style, conflicts }).into_response()
+}
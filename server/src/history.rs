@@ -0,0 +1,238 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{StatusCode, Uri},
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::csln;
+use crate::diff::{self, FieldChange, LineChange};
+use crate::metadata::{self, MetadataPatch};
+use crate::overlay;
+use crate::pagination::{Cursor, Page, PageParams};
+use crate::{auth, AppState, StyleRow};
+
+/// Records the current state of a style as its next numbered version.
+pub async fn record(
+    conn: &mut sqlx::PgConnection,
+    style: &StyleRow,
+    author_id: Uuid,
+) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO history (style_id, version, intent_snapshot, csln_snapshot, author_id)
+        VALUES ($1, COALESCE((SELECT MAX(version) FROM history WHERE style_id = $1), 0) + 1, $2, $3, $4)
+        RETURNING version
+        "#,
+        style.id,
+        style.intent,
+        style.csln,
+        author_id
+    )
+    .fetch_one(conn)
+    .await
+}
+
+async fn visible_style(state: &AppState, id: Uuid, user_id: Uuid) -> Option<StyleRow> {
+    sqlx::query_as!(
+        StyleRow,
+        "SELECT id, user_id, title, intent, csln, csln_source, is_public, revision, created_at, updated_at FROM styles WHERE id = $1 AND (user_id = $2 OR is_public = true) AND deleted_at IS NULL",
+        id,
+        user_id
+    )
+    .fetch_optional(&state.db)
+    .await
+    .expect("Failed to fetch style")
+}
+
+#[derive(Serialize)]
+pub struct HistoryEntry {
+    #[serde(skip)]
+    id: Uuid,
+    version: i32,
+    author_id: Option<Uuid>,
+    /// Only shown to the style's owner.
+    author_email: Option<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub async fn list_history(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path(id): Path<Uuid>,
+    uri: Uri,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let after = match page.after() {
+        Ok(after) => after,
+        Err(()) => return (StatusCode::BAD_REQUEST, "Invalid cursor").into_response(),
+    };
+    let style = match visible_style(&state, id, user.id).await {
+        Some(style) => style,
+        None => return (StatusCode::NOT_FOUND, "Style not found").into_response(),
+    };
+
+    let entries = sqlx::query_as!(
+        HistoryEntry,
+        r#"
+        SELECT h.id, h.version, h.author_id, CASE WHEN $5 THEN u.email END AS "author_email?", h.created_at
+        FROM history h
+        LEFT JOIN users u ON u.id = h.author_id
+        WHERE h.style_id = $1
+          AND ($2::BIGINT IS NULL OR (h.version::BIGINT, h.id) < ($2, $3::UUID))
+        ORDER BY h.version DESC, h.id DESC
+        LIMIT $4
+        "#,
+        id,
+        after.map(|c| c.key),
+        after.map(|c| c.id),
+        page.fetch_limit(),
+        style.user_id == user.id
+    )
+    .fetch_all(&state.db)
+    .await
+    .expect("Failed to fetch history");
+
+    Json(Page::new(entries, &page, &uri, |e| Cursor { key: e.version.into(), id: e.id })).into_response()
+}
+
+/// How a past version differs from the current one.
+#[derive(Serialize)]
+pub struct VersionDiff {
+    intent: Vec<FieldChange>,
+    csln: Vec<LineChange>,
+}
+
+#[derive(Serialize)]
+pub struct HistoryVersion {
+    version: i32,
+    author_id: Option<Uuid>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    intent: Value,
+    csln: Option<String>,
+    /// Changes from this version to the current one.
+    diff: VersionDiff,
+}
+
+pub async fn get_history_version(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path((id, version)): Path<(Uuid, i32)>,
+) -> impl IntoResponse {
+    let current = match visible_style(&state, id, user.id).await {
+        Some(style) => style,
+        None => return (StatusCode::NOT_FOUND, "Style not found").into_response(),
+    };
+
+    let snapshot = sqlx::query!(
+        "SELECT version, author_id, created_at, intent_snapshot, csln_snapshot FROM history WHERE style_id = $1 AND version = $2",
+        id,
+        version
+    )
+    .fetch_optional(&state.db)
+    .await
+    .expect("Failed to fetch history");
+
+    let snapshot = match snapshot {
+        Some(s) => s,
+        None => return (StatusCode::NOT_FOUND, "Version not found").into_response(),
+    };
+
+    let diff = VersionDiff {
+        intent: diff::json_changes(&snapshot.intent_snapshot, &current.intent),
+        csln: diff::line_diff(
+            snapshot.csln_snapshot.as_deref().unwrap_or_default(),
+            current.csln.as_deref().unwrap_or_default(),
+        ),
+    };
+
+    Json(HistoryVersion {
+        version: snapshot.version,
+        author_id: snapshot.author_id,
+        created_at: snapshot.created_at,
+        intent: snapshot.intent_snapshot,
+        csln: snapshot.csln_snapshot,
+        diff,
+    })
+    .into_response()
+}
+
+/// Restores a past version's intent and CSLN. The rollback is itself
+/// recorded as a new version, so it can be undone.
+pub async fn rollback(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path((id, version)): Path<(Uuid, i32)>,
+) -> impl IntoResponse {
+    let mut tx = state.db.begin().await.expect("Failed to start transaction");
+
+    let snapshot = sqlx::query!(
+        "SELECT intent_snapshot, csln_snapshot FROM history WHERE style_id = $1 AND version = $2",
+        id,
+        version
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("Failed to fetch history");
+
+    let snapshot = match snapshot {
+        Some(s) => s,
+        None => return (StatusCode::NOT_FOUND, "Version not found").into_response(),
+    };
+
+    // Snapshots predating server-side validation may hold invalid CSLN
+    let reconciled = match csln::reconcile(&snapshot.intent_snapshot, snapshot.csln_snapshot.as_deref()) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::UNPROCESSABLE_ENTITY, format!("Version {} can't be restored: {}", version, e)).into_response(),
+    };
+
+    // An overlay fork is read back through its overrides, so they have to
+    // describe the restored version too
+    let overrides = match overlay::layer(&mut tx, id).await {
+        Some(layer) => match overlay::overrides_for(&mut tx, &layer, &snapshot.intent_snapshot, snapshot.csln_snapshot.as_deref()).await {
+            Ok(overrides) => Some(overrides),
+            Err(e) => return (StatusCode::UNPROCESSABLE_ENTITY, format!("Version {} can't be restored: {}", version, e)).into_response(),
+        },
+        None => None,
+    };
+
+    let style = sqlx::query_as!(
+        StyleRow,
+        r#"
+        UPDATE styles
+        SET intent = $1, csln = $2, csln_source = $3, overrides = COALESCE($6, overrides)
+        WHERE id = $4 AND user_id = $5 AND deleted_at IS NULL
+        RETURNING id, user_id, title, intent, csln, csln_source, is_public, revision, created_at, updated_at
+        "#,
+        snapshot.intent_snapshot,
+        reconciled.csln,
+        reconciled.source,
+        id,
+        user.id,
+        overrides
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("Failed to roll back style");
+
+    let style = match style {
+        Some(s) => s,
+        None => return (StatusCode::NOT_FOUND, "Style not found").into_response(),
+    };
+
+    if let Err(e) = record(&mut tx, &style, user.id).await {
+        println!("Failed to record rollback of {}: {}", id, e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to record history").into_response();
+    }
+    if let Err(e) = metadata::refresh(&mut tx, &style, &MetadataPatch::default()).await {
+        println!("Failed to refresh metadata for {}: {}", style.id, e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update metadata").into_response();
+    }
+    tx.commit().await.expect("Failed to commit rollback");
+
+    Json(style).into_response()
+}
@@ -0,0 +1,211 @@
+use axum::{
+    extract::{Query, State},
+    http::{StatusCode, Uri},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::pagination::{Cursor, Page, PageParams};
+use crate::AppState;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HubSort {
+    /// Most recently released or updated first.
+    #[default]
+    Recent,
+    /// Most bookmarked and forked first.
+    Popular,
+}
+
+impl HubSort {
+    fn as_str(self) -> &'static str {
+        match self {
+            HubSort::Recent => "recent",
+            HubSort::Popular => "popular",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HubQuery {
+    /// Free text matched against titles, descriptions and disciplines.
+    q: Option<String>,
+    class: Option<String>,
+    discipline: Option<String>,
+    locale: Option<String>,
+    tag: Option<String>,
+    base_archetype: Option<String>,
+    #[serde(default)]
+    sort: HubSort,
+}
+
+/// A public style as listed in the hub: its latest release, or the draft
+/// for styles that have never been released.
+#[derive(Serialize)]
+pub struct HubStyle {
+    id: Uuid,
+    user_id: Uuid,
+    title: String,
+    intent: Value,
+    csln: Option<String>,
+    is_public: bool,
+    created_at: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+    /// The release shown, or `None` for an unreleased draft.
+    version: Option<String>,
+    description: String,
+    disciplines: Vec<String>,
+    tags: Vec<String>,
+    license: Option<String>,
+    locale: Option<String>,
+    citation_class: Option<String>,
+    /// Bookmarks plus forks.
+    popularity: i64,
+    #[serde(skip)]
+    sort_key: i64,
+}
+
+#[derive(Serialize)]
+pub struct FacetCount {
+    facet: String,
+    value: String,
+    count: i64,
+}
+
+#[derive(Serialize)]
+pub struct HubPage {
+    #[serde(flatten)]
+    page: Page<HubStyle>,
+    /// Counts per class, discipline, locale, tag and base archetype over
+    /// all matches.
+    facets: Vec<FacetCount>,
+}
+
+/// Searches the public styles. Metadata describes the style as a whole;
+/// the title and base archetype are those shown, i.e. the latest release's
+/// where there is one, so text search matches the listed title.
+pub async fn search(
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    Query(page): Query<PageParams>,
+    Query(query): Query<HubQuery>,
+) -> impl IntoResponse {
+    let after = match page.after() {
+        Ok(after) => after,
+        Err(()) => return (StatusCode::BAD_REQUEST, "Invalid cursor").into_response(),
+    };
+    let q = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+
+    let items = sqlx::query_as!(
+        HubStyle,
+        r#"
+        WITH hub AS (
+            SELECT
+                s.id, s.user_id,
+                COALESCE(r.title, s.title) AS title,
+                COALESCE(r.intent, s.intent) AS intent,
+                CASE WHEN r.version IS NULL THEN s.csln ELSE r.csln END AS csln,
+                s.is_public, s.created_at,
+                COALESCE(r.created_at, s.updated_at) AS updated_at,
+                r.version,
+                s.description, s.disciplines, s.tags, s.license, s.locale, s.citation_class,
+                (SELECT COUNT(*) FROM bookmarks b WHERE b.style_id = s.id)
+                    + (SELECT COUNT(*) FROM styles f WHERE f.forked_from = s.id AND f.deleted_at IS NULL) AS popularity
+            FROM styles s
+            LEFT JOIN LATERAL (
+                SELECT version, title, intent, csln, created_at
+                FROM releases
+                WHERE style_id = s.id
+                ORDER BY major DESC, minor DESC, patch DESC
+                LIMIT 1
+            ) r ON true
+            WHERE s.is_public = true AND s.deleted_at IS NULL
+              AND ($1::TEXT IS NULL OR s.search_vector @@ websearch_to_tsquery('simple', $1))
+        ), keyed AS (
+            SELECT *, CASE WHEN $5 = 'popular' THEN popularity
+                           ELSE (EXTRACT(EPOCH FROM updated_at) * 1000000)::BIGINT END AS sort_key
+            FROM hub
+            WHERE ($2::TEXT IS NULL OR citation_class = $2)
+              AND ($3::TEXT IS NULL OR $3 = ANY(disciplines))
+              AND ($4::TEXT IS NULL OR intent->>'base_archetype' = $4)
+              AND ($9::TEXT IS NULL OR locale = $9)
+              AND ($10::TEXT IS NULL OR $10 = ANY(tags))
+        )
+        SELECT
+            id AS "id!", user_id AS "user_id!", title AS "title!", intent AS "intent!", csln,
+            is_public AS "is_public!", created_at AS "created_at!", updated_at AS "updated_at!",
+            version AS "version?",
+            description AS "description!", disciplines AS "disciplines!", tags AS "tags!",
+            license, locale, citation_class,
+            popularity AS "popularity!", sort_key AS "sort_key!"
+        FROM keyed
+        WHERE ($6::BIGINT IS NULL OR (sort_key, id) < ($6, $7::UUID))
+        ORDER BY sort_key DESC, id DESC
+        LIMIT $8
+        "#,
+        q,
+        query.class,
+        query.discipline,
+        query.base_archetype,
+        query.sort.as_str(),
+        after.map(|c| c.key),
+        after.map(|c| c.id),
+        page.fetch_limit(),
+        query.locale,
+        query.tag
+    )
+    .fetch_all(&state.db)
+    .await
+    .expect("Failed to search hub");
+
+    let facets = sqlx::query_as!(
+        FacetCount,
+        r#"
+        WITH matched AS (
+            SELECT COALESCE(r.intent, s.intent) AS intent, s.citation_class, s.disciplines, s.tags, s.locale
+            FROM styles s
+            LEFT JOIN LATERAL (
+                SELECT intent FROM releases
+                WHERE style_id = s.id
+                ORDER BY major DESC, minor DESC, patch DESC
+                LIMIT 1
+            ) r ON true
+            WHERE s.is_public = true AND s.deleted_at IS NULL
+              AND ($1::TEXT IS NULL OR s.search_vector @@ websearch_to_tsquery('simple', $1))
+              AND ($2::TEXT IS NULL OR s.citation_class = $2)
+              AND ($3::TEXT IS NULL OR $3 = ANY(s.disciplines))
+              AND ($4::TEXT IS NULL OR COALESCE(r.intent, s.intent)->>'base_archetype' = $4)
+              AND ($5::TEXT IS NULL OR s.locale = $5)
+              AND ($6::TEXT IS NULL OR $6 = ANY(s.tags))
+        ), facet_values AS (
+            SELECT 'class' AS facet, citation_class AS value FROM matched
+            UNION ALL SELECT 'discipline', unnest(disciplines) FROM matched
+            UNION ALL SELECT 'tag', unnest(tags) FROM matched
+            UNION ALL SELECT 'locale', locale FROM matched
+            UNION ALL SELECT 'base_archetype', intent->>'base_archetype' FROM matched
+        )
+        SELECT facet AS "facet!", value AS "value!", COUNT(*) AS "count!"
+        FROM facet_values
+        WHERE value IS NOT NULL
+        GROUP BY facet, value
+        ORDER BY facet, COUNT(*) DESC, value
+        "#,
+        q,
+        query.class,
+        query.discipline,
+        query.base_archetype,
+        query.locale,
+        query.tag
+    )
+    .fetch_all(&state.db)
+    .await
+    .expect("Failed to count hub facets");
+
+    let page = Page::new(items, &page, &uri, |s| Cursor { key: s.sort_key, id: s.id });
+    Json(HubPage { page, facets }).into_response()
+}
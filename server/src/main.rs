@@ -1,4 +1,14 @@
 mod auth;
+mod csln;
+mod diff;
+mod forks;
+mod history;
+mod hub;
+mod metadata;
+mod overlay;
+mod pagination;
+mod releases;
+mod trash;
 
 use axum::{
     extract::{State, Query},
@@ -57,6 +67,8 @@ async fn main() {
     let f = std::fs::File::open(ref_path).expect("Failed to open comprehensive.yaml references");
     let references: HashMap<String, Reference> = serde_yaml::from_reader(f).expect("Failed to parse comprehensive.yaml");
 
+    trash::spawn_purge_job(pool.clone());
+
     let state = Arc::new(AppState {
         references,
         db: pool,
@@ -72,11 +84,24 @@ async fn main() {
         .route("/preview/citation", post(preview_citation))
         .route("/preview/bibliography", post(preview_bibliography))
         .route("/api/styles", get(list_styles).post(save_style))
-        .route("/api/styles/:id", get(get_style))
-        .route("/api/styles/:id/fork", post(fork_style))
+        .route("/api/styles/import", post(import_style))
+        .route("/api/styles/:id", get(get_style).delete(trash::delete_style))
+        .route("/api/styles/:id/restore", post(trash::restore_style))
+        .route("/api/styles/:id/fork", post(forks::fork_style))
+        .route("/api/styles/:id/forks", get(forks::list_forks))
+        .route("/api/styles/:id/upstream", get(forks::upstream_status))
+        .route("/api/styles/:id/upstream/merge", post(forks::merge_upstream))
+        .route("/api/styles/:id/export/csl", get(export_style_csl))
+        .route("/api/styles/:id/metadata", get(metadata::get_metadata).patch(metadata::update_metadata))
+        .route("/api/styles/:id/history", get(history::list_history))
+        .route("/api/styles/:id/history/:version", get(history::get_history_version))
+        .route("/api/styles/:id/rollback/:version", post(history::rollback))
+        .route("/api/styles/:id/versions", get(releases::list_releases).post(releases::publish))
+        .route("/api/styles/:id/versions/:semver", get(releases::get_release))
         .route("/api/styles/:id/bookmark", post(add_bookmark).delete(remove_bookmark))
         .route("/api/bookmarks", get(list_bookmarks))
-        .route("/api/hub", get(list_public_styles))
+        .route("/api/trash", get(trash::list_trash))
+        .route("/api/hub", get(hub::search))
         .with_state(state)
         .layer(tower_http::cors::CorsLayer::permissive());
 
@@ -232,7 +257,13 @@ struct StyleRow {
     title: String,
     intent: Value,
     csln: Option<String>,
+    /// `intent` when the CSLN is generated from the intent, `csln` when it
+    /// was edited by hand.
+    csln_source: String,
     is_public: bool,
+    /// Bumped whenever the content changes, but not by metadata; sent as
+    /// the style's ETag.
+    revision: i32,
     created_at: chrono::DateTime<chrono::Utc>,
     updated_at: chrono::DateTime<chrono::Utc>,
 }
@@ -242,38 +273,69 @@ struct SaveStyleRequest {
     id: Option<Uuid>,
     title: String,
     intent: Value,
+    /// Hand-edited CSLN. When omitted, a stored hand edit is kept and
+    /// anything else is generated from the intent; send an empty string to
+    /// drop a hand edit.
     csln: Option<String>,
     is_public: Option<bool>,
+    /// The revision the edit was based on, for clients that can't send
+    /// `If-Match`. The header wins when both are present.
+    revision: Option<i32>,
+    #[serde(default)]
+    metadata: metadata::MetadataPatch,
 }
 
-async fn list_styles(
-    State(state): State<Arc<AppState>>,
-    user: auth::AuthenticatedUser,
-) -> Json<Vec<StyleRow>> {
-    let styles = sqlx::query_as!(
-        StyleRow,
-        "SELECT id, user_id, title, intent, csln, is_public, created_at, updated_at FROM styles WHERE user_id = $1 ORDER BY updated_at DESC",
-        user.id
-    )
-    .fetch_all(&state.db)
-    .await
-    .expect("Failed to fetch styles");
+impl StyleRow {
+    fn etag(&self) -> String {
+        format!("\"{}\"", self.revision)
+    }
+}
 
-    Json(styles)
+/// The revision an `If-Match` header requires, or `None` when absent or `*`.
+/// Malformed values are rejected rather than ignored, so a client bug can't
+/// silently disable the check.
+fn if_match(headers: &axum::http::HeaderMap) -> Result<Option<i32>, ()> {
+    let value = match headers.get(axum::http::header::IF_MATCH) {
+        Some(v) => v.to_str().map_err(|_| ())?.trim(),
+        None => return Ok(None),
+    };
+    if value == "*" {
+        return Ok(None);
+    }
+    value.trim_start_matches("W/").trim_matches('"').parse().map(Some).map_err(|_| ())
 }
 
-async fn list_public_styles(
+async fn list_styles(
     State(state): State<Arc<AppState>>,
-) -> Json<Vec<StyleRow>> {
+    user: auth::AuthenticatedUser,
+    uri: axum::http::Uri,
+    Query(page): Query<pagination::PageParams>,
+) -> impl IntoResponse {
+    let after = match page.after() {
+        Ok(after) => after,
+        Err(()) => return (axum::http::StatusCode::BAD_REQUEST, "Invalid cursor").into_response(),
+    };
+
     let styles = sqlx::query_as!(
         StyleRow,
-        "SELECT id, user_id, title, intent, csln, is_public, created_at, updated_at FROM styles WHERE is_public = true ORDER BY updated_at DESC"
+        r#"
+        SELECT id, user_id, title, intent, csln, csln_source, is_public, revision, created_at, updated_at
+        FROM styles
+        WHERE user_id = $1 AND deleted_at IS NULL
+          AND ($2::BIGINT IS NULL OR ((EXTRACT(EPOCH FROM updated_at) * 1000000)::BIGINT, id) < ($2, $3::UUID))
+        ORDER BY updated_at DESC, id DESC
+        LIMIT $4
+        "#,
+        user.id,
+        after.map(|c| c.key),
+        after.map(|c| c.id),
+        page.fetch_limit()
     )
     .fetch_all(&state.db)
     .await
-    .expect("Failed to fetch public styles");
+    .expect("Failed to fetch styles");
 
-    Json(styles)
+    Json(pagination::Page::new(styles, &page, &uri, |s| pagination::Cursor::at_time(s.updated_at, s.id))).into_response()
 }
 
 async fn get_style(
@@ -283,7 +345,7 @@ async fn get_style(
 ) -> impl IntoResponse {
     let style = sqlx::query_as!(
         StyleRow,
-        "SELECT id, user_id, title, intent, csln, is_public, created_at, updated_at FROM styles WHERE id = $1 AND (user_id = $2 OR is_public = true)",
+        "SELECT id, user_id, title, intent, csln, csln_source, is_public, revision, created_at, updated_at FROM styles WHERE id = $1 AND (user_id = $2 OR is_public = true) AND deleted_at IS NULL",
         id,
         user.id
     )
@@ -291,35 +353,94 @@ async fn get_style(
     .await
     .expect("Failed to fetch style");
 
-    match style {
-        Some(s) => Json(s).into_response(),
-        None => (axum::http::StatusCode::NOT_FOUND, "Style not found").into_response(),
+    let mut style = match style {
+        Some(s) => s,
+        None => return (axum::http::StatusCode::NOT_FOUND, "Style not found").into_response(),
+    };
+
+    // Overlay forks are resolved against their pinned parent on every read;
+    // the stored copy is only a fallback if that version is gone
+    let mut conn = state.db.acquire().await.expect("Failed to acquire connection");
+    if let Some(layer) = overlay::layer(&mut conn, id).await {
+        match overlay::resolve(&mut conn, &layer).await {
+            Ok((intent, reconciled)) => {
+                style.intent = intent;
+                style.csln = Some(reconciled.csln);
+                style.csln_source = reconciled.source.to_string();
+            },
+            Err(e) => println!("Failed to resolve overlay {}: {}", id, e),
+        }
     }
+
+    ([(axum::http::header::ETAG, style.etag())], Json(style)).into_response()
 }
 
 async fn save_style(
     State(state): State<Arc<AppState>>,
     user: auth::AuthenticatedUser,
+    headers: axum::http::HeaderMap,
     Json(payload): Json<SaveStyleRequest>,
-) -> Json<StyleRow> {
+) -> impl IntoResponse {
+    let expected = match if_match(&headers) {
+        Ok(header) => header.or(payload.revision),
+        Err(()) => return (axum::http::StatusCode::BAD_REQUEST, "If-Match must be a quoted revision").into_response(),
+    };
+
+    // The style and its history entry are written together
+    let mut tx = state.db.begin().await.expect("Failed to start transaction");
+
+    // A save that leaves out the CSLN keeps a hand-edited one
+    let stored_csln = match (payload.id, &payload.csln) {
+        (Some(id), None) => sqlx::query_scalar!(
+            "SELECT csln FROM styles WHERE id = $1 AND user_id = $2 AND csln_source = $3 AND deleted_at IS NULL",
+            id,
+            user.id,
+            csln::SOURCE_CSLN
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .expect("Failed to fetch style")
+        .flatten(),
+        _ => None,
+    };
+    let reconciled = match csln::reconcile(&payload.intent, payload.csln.as_deref().or(stored_csln.as_deref())) {
+        Ok(r) => r,
+        Err(e) => return (axum::http::StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+    };
+
+    // Edits to an overlay fork are stored as overrides of its pinned parent
+    let layer = match payload.id {
+        Some(id) => overlay::layer(&mut tx, id).await,
+        None => None,
+    };
+    let overrides = match &layer {
+        Some(layer) => match overlay::overrides_for(&mut tx, layer, &payload.intent, payload.csln.as_deref()).await {
+            Ok(overrides) => Some(overrides),
+            Err(e) => return (axum::http::StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+        },
+        None => None,
+    };
     let style = if let Some(id) = payload.id {
         // Update
         sqlx::query_as!(
             StyleRow,
             r#"
             UPDATE styles 
-            SET title = $1, intent = $2, csln = $3, is_public = $4
-            WHERE id = $5 AND user_id = $6
-            RETURNING id, user_id, title, intent, csln, is_public, created_at, updated_at
+            SET title = $1, intent = $2, csln = $3, csln_source = $4, is_public = $5, overrides = $9
+            WHERE id = $6 AND user_id = $7 AND deleted_at IS NULL AND ($8::INTEGER IS NULL OR revision = $8)
+            RETURNING id, user_id, title, intent, csln, csln_source, is_public, revision, created_at, updated_at
             "#,
             payload.title,
             payload.intent,
-            payload.csln,
+            reconciled.csln,
+            reconciled.source,
             payload.is_public.unwrap_or(false),
             id,
-            user.id
+            user.id,
+            expected,
+            overrides
         )
-        .fetch_one(&state.db)
+        .fetch_optional(&mut *tx)
         .await
         .expect("Failed to update style")
     } else {
@@ -327,72 +448,167 @@ async fn save_style(
         sqlx::query_as!(
             StyleRow,
             r#"
-            INSERT INTO styles (user_id, title, intent, csln, is_public)
-            VALUES ($1, $2, $3, $4, $5)
-            RETURNING id, user_id, title, intent, csln, is_public, created_at, updated_at
+            INSERT INTO styles (user_id, title, intent, csln, csln_source, is_public)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, title, intent, csln, csln_source, is_public, revision, created_at, updated_at
             "#,
             user.id,
             payload.title,
             payload.intent,
-            payload.csln,
+            reconciled.csln,
+            reconciled.source,
             payload.is_public.unwrap_or(false)
         )
-        .fetch_one(&state.db)
+        .fetch_one(&mut *tx)
         .await
+        .map(Some)
         .expect("Failed to create style")
     };
 
-    // Add to history
-    let _ = sqlx::query!(
-        "INSERT INTO history (style_id, intent_snapshot) VALUES ($1, $2)",
-        style.id,
-        style.intent
-    )
-    .execute(&state.db)
-    .await;
+    let style = match style {
+        Some(s) => s,
+        None => {
+            // Either someone else saved first, or the style isn't ours
+            let current = sqlx::query_as!(
+                StyleRow,
+                "SELECT id, user_id, title, intent, csln, csln_source, is_public, revision, created_at, updated_at FROM styles WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL",
+                payload.id,
+                user.id
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .expect("Failed to fetch style");
+
+            return match current {
+                Some(current) => (
+                    axum::http::StatusCode::CONFLICT,
+                    [(axum::http::header::ETAG, current.etag())],
+                    Json(current),
+                )
+                    .into_response(),
+                None => (axum::http::StatusCode::NOT_FOUND, "Style not found").into_response(),
+            };
+        },
+    };
+
+    if let Err(e) = history::record(&mut tx, &style, user.id).await {
+        println!("Failed to record history for {}: {}", style.id, e);
+        return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, "Failed to record history").into_response();
+    }
+    if let Err(e) = metadata::refresh(&mut tx, &style, &payload.metadata).await {
+        println!("Failed to refresh metadata for {}: {}", style.id, e);
+        return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, "Failed to update metadata").into_response();
+    }
+    tx.commit().await.expect("Failed to commit style");
 
-    Json(style)
+    ([(axum::http::header::ETAG, style.etag())], Json(style)).into_response()
 }
 
-async fn fork_style(
+#[derive(Deserialize)]
+struct ImportStyleRequest {
+    /// A CSL 1.0 `.csl` file.
+    xml: String,
+    is_public: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct ImportStyleResponse {
+    style: StyleRow,
+    /// CSL features that could not be carried over.
+    warnings: Vec<String>,
+}
+
+async fn import_style(
     State(state): State<Arc<AppState>>,
     user: auth::AuthenticatedUser,
-    axum::extract::Path(id): axum::extract::Path<Uuid>,
+    Json(payload): Json<ImportStyleRequest>,
 ) -> impl IntoResponse {
-    // 1. Fetch the original style (must be public or owned by user)
-    let original = sqlx::query!(
-        "SELECT title, intent, csln FROM styles WHERE id = $1 AND (is_public = true OR user_id = $2)",
-        id,
-        user.id
-    )
-    .fetch_optional(&state.db)
-    .await
-    .expect("Database error");
+    let import = match intent_engine::csl::import(&payload.xml) {
+        Ok(import) => import,
+        Err(e) => return (axum::http::StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response(),
+    };
 
-    let original = match original {
-        Some(s) => s,
-        None => return (axum::http::StatusCode::NOT_FOUND, "Source style not found").into_response(),
+    let title = import.style.info.title.clone().unwrap_or_else(|| "Imported Style".to_string());
+    let intent = serde_json::to_value(&import.intent).expect("Failed to serialize intent");
+    let csln = serde_yaml::to_string(&import.style).expect("Failed to serialize style");
+    // The imported CSLN carries the CSL title and id, so it usually counts as a hand edit
+    let reconciled = match csln::reconcile(&intent, Some(&csln)) {
+        Ok(r) => r,
+        Err(e) => return (axum::http::StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
     };
 
-    // 2. Create the copy
-    let forked_title = format!("{} (Fork)", original.title);
+    let mut tx = state.db.begin().await.expect("Failed to start transaction");
     let style = sqlx::query_as!(
         StyleRow,
         r#"
-        INSERT INTO styles (user_id, title, intent, csln, is_public)
-        VALUES ($1, $2, $3, $4, false)
-        RETURNING id, user_id, title, intent, csln, is_public, created_at, updated_at
+        INSERT INTO styles (user_id, title, intent, csln, csln_source, is_public)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, user_id, title, intent, csln, csln_source, is_public, revision, created_at, updated_at
         "#,
         user.id,
-        forked_title,
-        original.intent,
-        original.csln,
+        title,
+        intent,
+        reconciled.csln,
+        reconciled.source,
+        payload.is_public.unwrap_or(false)
     )
-    .fetch_one(&state.db)
+    .fetch_one(&mut *tx)
+    .await
+    .expect("Failed to import style");
+
+    if let Err(e) = history::record(&mut tx, &style, user.id).await {
+        println!("Failed to record history for {}: {}", style.id, e);
+        return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, "Failed to record history").into_response();
+    }
+    if let Err(e) = metadata::refresh(&mut tx, &style, &metadata::MetadataPatch::default()).await {
+        println!("Failed to refresh metadata for {}: {}", style.id, e);
+        return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, "Failed to update metadata").into_response();
+    }
+    tx.commit().await.expect("Failed to commit import");
+
+    (axum::http::StatusCode::CREATED, Json(ImportStyleResponse { style, warnings: import.warnings })).into_response()
+}
+
+async fn export_style_csl(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    axum::extract::Path(id): axum::extract::Path<Uuid>,
+) -> impl IntoResponse {
+    let row = sqlx::query!(
+        "SELECT title, intent, csln FROM styles WHERE id = $1 AND (user_id = $2 OR is_public = true) AND deleted_at IS NULL",
+        id,
+        user.id
+    )
+    .fetch_optional(&state.db)
     .await
-    .expect("Failed to fork style");
+    .expect("Failed to fetch style");
+
+    let mut row = match row {
+        Some(row) => row,
+        None => return (axum::http::StatusCode::NOT_FOUND, "Style not found").into_response(),
+    };
 
-    Json(style).into_response()
+    // Overlay forks download flattened, with their overrides applied
+    let mut conn = state.db.acquire().await.expect("Failed to acquire connection");
+    if let Some(layer) = overlay::layer(&mut conn, id).await {
+        if let Ok((intent, reconciled)) = overlay::resolve(&mut conn, &layer).await {
+            row.intent = intent;
+            row.csln = Some(reconciled.csln);
+        }
+    }
+
+    // Prefer the stored CSLN; fall back to regenerating it from the intent
+    let intent: Option<intent_engine::StyleIntent> = serde_json::from_value(row.intent).ok();
+    let style = row.csln.as_deref()
+        .and_then(|csln| serde_yaml::from_str::<Style>(csln).ok())
+        .or_else(|| intent.as_ref().map(|intent| intent.to_style()));
+    let mut style = match style {
+        Some(style) => style,
+        None => return (axum::http::StatusCode::UNPROCESSABLE_ENTITY, "Style has no usable CSLN or intent").into_response(),
+    };
+    style.info.title = Some(row.title);
+
+    Json(intent_engine::csl::export(&style, intent.as_ref())).into_response()
 }
 
 async fn add_bookmark(
@@ -401,7 +617,11 @@ async fn add_bookmark(
     axum::extract::Path(id): axum::extract::Path<Uuid>,
 ) -> impl IntoResponse {
     let result = sqlx::query!(
-        "INSERT INTO bookmarks (user_id, style_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        r#"
+        INSERT INTO bookmarks (user_id, style_id)
+        SELECT $1, id FROM styles WHERE id = $2 AND (user_id = $1 OR is_public = true) AND deleted_at IS NULL
+        ON CONFLICT DO NOTHING
+        "#,
         user.id,
         id
     )
@@ -430,24 +650,105 @@ async fn remove_bookmark(
     axum::http::StatusCode::NO_CONTENT
 }
 
+#[derive(Serialize)]
+struct Bookmark {
+    #[serde(flatten)]
+    style: StyleRow,
+    bookmarked_at: chrono::DateTime<chrono::Utc>,
+}
+
 async fn list_bookmarks(
     State(state): State<Arc<AppState>>,
     user: auth::AuthenticatedUser,
-) -> Json<Vec<StyleRow>> {
-    let styles = sqlx::query_as!(
-        StyleRow,
+    uri: axum::http::Uri,
+    Query(page): Query<pagination::PageParams>,
+) -> impl IntoResponse {
+    let after = match page.after() {
+        Ok(after) => after,
+        Err(()) => return (axum::http::StatusCode::BAD_REQUEST, "Invalid cursor").into_response(),
+    };
+
+    let rows = sqlx::query!(
         r#"
-        SELECT s.id, s.user_id, s.title, s.intent, s.csln, s.is_public, s.created_at, s.updated_at 
+        SELECT s.id, s.user_id, s.title, s.intent, s.csln, s.csln_source, s.is_public, s.revision, s.created_at, s.updated_at,
+               b.created_at AS bookmarked_at
         FROM styles s
         JOIN bookmarks b ON s.id = b.style_id
-        WHERE b.user_id = $1
-        ORDER BY b.created_at DESC
+        -- Bookmarks of trashed styles reappear if the style is restored
+        WHERE b.user_id = $1 AND s.deleted_at IS NULL AND (s.is_public = true OR s.user_id = $1)
+          AND ($2::BIGINT IS NULL OR ((EXTRACT(EPOCH FROM b.created_at) * 1000000)::BIGINT, s.id) < ($2, $3::UUID))
+        ORDER BY b.created_at DESC, s.id DESC
+        LIMIT $4
         "#,
-        user.id
+        user.id,
+        after.map(|c| c.key),
+        after.map(|c| c.id),
+        page.fetch_limit()
     )
     .fetch_all(&state.db)
     .await
     .expect("Failed to fetch bookmarks");
 
-    Json(styles)
+    let bookmarks = rows
+        .into_iter()
+        .map(|r| Bookmark {
+            style: StyleRow {
+                id: r.id,
+                user_id: r.user_id,
+                title: r.title,
+                intent: r.intent,
+                csln: r.csln,
+                csln_source: r.csln_source,
+                is_public: r.is_public,
+                revision: r.revision,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+            },
+            bookmarked_at: r.bookmarked_at,
+        })
+        .collect();
+
+    Json(pagination::Page::new(bookmarks, &page, &uri, |b: &Bookmark| pagination::Cursor::at_time(b.bookmarked_at, b.style.id))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{header::IF_MATCH, HeaderMap, HeaderValue};
+
+    fn style(revision: i32) -> StyleRow {
+        StyleRow {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            title: "Style".to_string(),
+            intent: serde_json::json!({}),
+            csln: None,
+            csln_source: csln::SOURCE_INTENT.to_string(),
+            is_public: false,
+            revision,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    fn headers(if_match: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_MATCH, HeaderValue::from_str(if_match).unwrap());
+        headers
+    }
+
+    #[test]
+    fn a_saved_etag_can_be_sent_back_as_if_match() {
+        // Save, then save again with the ETag the first save returned
+        let saved = style(7);
+        assert_eq!(if_match(&headers(&saved.etag())), Ok(Some(7)));
+        assert_eq!(if_match(&headers(&format!("W/{}", saved.etag()))), Ok(Some(7)));
+    }
+
+    #[test]
+    fn if_match_is_optional_but_must_be_well_formed() {
+        assert_eq!(if_match(&HeaderMap::new()), Ok(None));
+        assert_eq!(if_match(&headers("*")), Ok(None));
+        assert_eq!(if_match(&headers("\"seven\"")), Err(()));
+    }
 }
@@ -0,0 +1,162 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{auth, AppState, StyleRow};
+
+/// Descriptive metadata about a style. The citation class always follows
+/// the intent; everything else is the owner's to edit.
+#[derive(Serialize)]
+pub struct StyleMetadata {
+    description: String,
+    disciplines: Vec<String>,
+    tags: Vec<String>,
+    /// An SPDX identifier, e.g. "CC-BY-SA-3.0".
+    license: Option<String>,
+    /// A BCP 47 tag, e.g. "en-US".
+    locale: Option<String>,
+    citation_class: Option<String>,
+    documentation_url: Option<String>,
+}
+
+/// Metadata edits; absent fields are left alone and an empty string clears
+/// an optional one.
+#[derive(Deserialize, Default)]
+pub struct MetadataPatch {
+    description: Option<String>,
+    disciplines: Option<Vec<String>>,
+    tags: Option<Vec<String>>,
+    license: Option<String>,
+    locale: Option<String>,
+    documentation_url: Option<String>,
+}
+
+/// Reads a string from the CSLN `info` block, if the document has one.
+fn info_field(csln: Option<&str>, key: &str) -> Option<String> {
+    let style: serde_yaml::Value = serde_yaml::from_str(csln?).ok()?;
+    style.get("info")?.get(key)?.as_str().map(str::to_string)
+}
+
+/// Applies `patch` and re-derives what the intent and CSLN determine: the
+/// citation class always, and the discipline, description and locale when
+/// the style doesn't have its own yet.
+pub async fn refresh(conn: &mut sqlx::PgConnection, style: &StyleRow, patch: &MetadataPatch) -> Result<(), sqlx::Error> {
+    let class = style.intent.get("class").and_then(Value::as_str);
+    let field = style.intent.get("field").and_then(Value::as_str);
+    let csln = style.csln.as_deref();
+
+    sqlx::query!(
+        r#"
+        UPDATE styles SET
+            citation_class = $2,
+            disciplines = COALESCE($3, CASE WHEN disciplines = '{}' AND $4::TEXT IS NOT NULL THEN ARRAY[$4] ELSE disciplines END),
+            description = COALESCE($5, CASE WHEN description = '' THEN COALESCE($6, '') ELSE description END),
+            tags = COALESCE($7, tags),
+            license = NULLIF(COALESCE($8, license), ''),
+            locale = NULLIF(COALESCE($9, locale, $10), ''),
+            documentation_url = NULLIF(COALESCE($11, documentation_url), '')
+        WHERE id = $1
+        "#,
+        style.id,
+        class,
+        patch.disciplines.as_deref(),
+        field,
+        patch.description,
+        info_field(csln, "description"),
+        patch.tags.as_deref(),
+        patch.license,
+        patch.locale,
+        info_field(csln, "default-locale"),
+        patch.documentation_url
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Gives a fork its parent's metadata.
+pub async fn copy(conn: &mut sqlx::PgConnection, from: Uuid, to: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE styles f SET
+            description = p.description, disciplines = p.disciplines, tags = p.tags,
+            license = p.license, locale = p.locale, documentation_url = p.documentation_url
+        FROM styles p
+        WHERE f.id = $1 AND p.id = $2
+        "#,
+        to,
+        from
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_metadata(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let metadata = sqlx::query_as!(
+        StyleMetadata,
+        r#"
+        SELECT description, disciplines, tags, license, locale, citation_class, documentation_url
+        FROM styles
+        WHERE id = $1 AND (user_id = $2 OR is_public = true) AND deleted_at IS NULL
+        "#,
+        id,
+        user.id
+    )
+    .fetch_optional(&state.db)
+    .await
+    .expect("Failed to fetch metadata");
+
+    match metadata {
+        Some(m) => Json(m).into_response(),
+        None => (StatusCode::NOT_FOUND, "Style not found").into_response(),
+    }
+}
+
+pub async fn update_metadata(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path(id): Path<Uuid>,
+    Json(patch): Json<MetadataPatch>,
+) -> impl IntoResponse {
+    let mut tx = state.db.begin().await.expect("Failed to start transaction");
+
+    let style = sqlx::query_as!(
+        StyleRow,
+        "SELECT id, user_id, title, intent, csln, csln_source, is_public, revision, created_at, updated_at FROM styles WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL",
+        id,
+        user.id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("Failed to fetch style");
+
+    let style = match style {
+        Some(s) => s,
+        None => return (StatusCode::NOT_FOUND, "Style not found").into_response(),
+    };
+
+    refresh(&mut tx, &style, &patch).await.expect("Failed to update metadata");
+    let metadata = sqlx::query_as!(
+        StyleMetadata,
+        "SELECT description, disciplines, tags, license, locale, citation_class, documentation_url FROM styles WHERE id = $1",
+        id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .expect("Failed to fetch metadata");
+    tx.commit().await.expect("Failed to commit metadata");
+
+    Json(metadata).into_response()
+}
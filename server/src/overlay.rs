@@ -0,0 +1,193 @@
+use intent_engine::StyleIntent;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::csln::{self, Reconciled, SOURCE_CSLN};
+use crate::diff;
+
+/// An overlay fork's pinned parent version and its merge patches.
+pub struct Layer {
+    pub parent_id: Uuid,
+    pub version: i32,
+    pub overrides: Value,
+}
+
+/// The overlay a style is, if it's an overlay fork whose parent still exists.
+pub async fn layer(conn: &mut sqlx::PgConnection, id: Uuid) -> Option<Layer> {
+    let row = sqlx::query!(
+        "SELECT forked_from, forked_from_version, overrides FROM styles WHERE id = $1",
+        id
+    )
+    .fetch_optional(conn)
+    .await
+    .expect("Failed to fetch style")?;
+
+    Some(Layer {
+        parent_id: row.forked_from?,
+        version: row.forked_from_version?,
+        overrides: row.overrides?,
+    })
+}
+
+struct Parent {
+    intent: Value,
+    csln: Option<String>,
+}
+
+async fn parent_snapshot(conn: &mut sqlx::PgConnection, parent_id: Uuid, version: i32) -> Result<Parent, String> {
+    sqlx::query!(
+        "SELECT intent_snapshot, csln_snapshot FROM history WHERE style_id = $1 AND version = $2",
+        parent_id,
+        version
+    )
+    .fetch_optional(conn)
+    .await
+    .expect("Failed to fetch history")
+    .map(|row| Parent { intent: row.intent_snapshot, csln: row.csln_snapshot })
+    .ok_or_else(|| format!("Parent version {} no longer exists", version))
+}
+
+/// The CSLN the fork's own CSLN patch applies to. A parent whose CSLN is
+/// generated is regenerated from the fork's intent, so intent overrides
+/// show up in it; a hand-edited parent CSLN is used as-is.
+fn base_csln(parent: &Parent, intent: &Value) -> Result<String, String> {
+    let parent_hand_edited = csln::reconcile(&parent.intent, parent.csln.as_deref())
+        .map(|r| r.source == SOURCE_CSLN)
+        .unwrap_or(false);
+    match (&parent.csln, parent_hand_edited) {
+        (Some(csln), true) => Ok(csln.clone()),
+        _ => csln::reconcile(intent, None).map(|r| r.csln),
+    }
+}
+
+fn yaml_value(csln: &str) -> Result<Value, String> {
+    let value: serde_yaml::Value = serde_yaml::from_str(csln).map_err(|e| format!("Invalid CSLN: {}", e))?;
+    serde_json::to_value(untagged(value)).map_err(|e| format!("Invalid CSLN: {}", e))
+}
+
+fn yaml_text(value: &Value) -> Result<String, String> {
+    let value = serde_yaml::to_value(value).map_err(|e| format!("Failed to serialize CSLN: {}", e))?;
+    serde_yaml::to_string(&tagged(value)).map_err(|e| format!("Failed to serialize CSLN: {}", e))
+}
+
+/// Rewrites YAML enum tags (`!Custom {…}`) as single-key maps keyed by the
+/// tag, which JSON can hold and merge patches can reach into.
+fn untagged(value: serde_yaml::Value) -> serde_yaml::Value {
+    use serde_yaml::Value as Yaml;
+    match value {
+        Yaml::Tagged(tagged) => {
+            let key = Yaml::String(tagged.tag.to_string());
+            Yaml::Mapping([(key, untagged(tagged.value))].into_iter().collect())
+        },
+        Yaml::Mapping(map) => Yaml::Mapping(map.into_iter().map(|(k, v)| (k, untagged(v))).collect()),
+        Yaml::Sequence(items) => Yaml::Sequence(items.into_iter().map(untagged).collect()),
+        other => other,
+    }
+}
+
+/// Undoes `untagged`.
+fn tagged(value: serde_yaml::Value) -> serde_yaml::Value {
+    use serde_yaml::value::{Tag, TaggedValue};
+    use serde_yaml::Value as Yaml;
+    match value {
+        Yaml::Mapping(map) => {
+            let tag = match map.iter().next() {
+                Some((Yaml::String(key), _)) if map.len() == 1 && key.starts_with('!') => Some(Tag::new(key)),
+                _ => None,
+            };
+            match tag {
+                Some(tag) => {
+                    let value = map.into_iter().next().map(|(_, v)| tagged(v)).unwrap_or(Yaml::Null);
+                    Yaml::Tagged(Box::new(TaggedValue { tag, value }))
+                },
+                None => Yaml::Mapping(map.into_iter().map(|(k, v)| (k, tagged(v))).collect()),
+            }
+        },
+        Yaml::Sequence(items) => Yaml::Sequence(items.into_iter().map(tagged).collect()),
+        other => other,
+    }
+}
+
+/// Applies a fork's overrides to its pinned parent version, giving the
+/// flattened intent and CSLN.
+pub async fn resolve(conn: &mut sqlx::PgConnection, layer: &Layer) -> Result<(Value, Reconciled), String> {
+    let parent = parent_snapshot(conn, layer.parent_id, layer.version).await?;
+    apply(&parent, &layer.overrides)
+}
+
+fn apply(parent: &Parent, overrides: &Value) -> Result<(Value, Reconciled), String> {
+    let mut intent = parent.intent.clone();
+    if let Some(patch) = overrides.get("intent") {
+        diff::merge_patch(&mut intent, patch);
+    }
+    // Round-trip so keys the patch removed come back as explicit nulls
+    let intent = serde_json::from_value::<StyleIntent>(intent)
+        .and_then(serde_json::to_value)
+        .map_err(|e| format!("Invalid intent: {}", e))?;
+
+    let mut csln = base_csln(parent, &intent)?;
+    if let Some(patch) = overrides.get("csln").filter(|p| p.as_object().is_some_and(|p| !p.is_empty())) {
+        let mut value = yaml_value(&csln)?;
+        diff::merge_patch(&mut value, patch);
+        csln = yaml_text(&value)?;
+    }
+
+    let reconciled = csln::reconcile(&intent, Some(&csln))?;
+    Ok((intent, reconciled))
+}
+
+/// The overrides that make the layer's pinned parent version look like the
+/// given intent and CSLN. Without CSLN, the layer's CSLN patch is kept.
+pub async fn overrides_for(
+    conn: &mut sqlx::PgConnection,
+    layer: &Layer,
+    intent: &Value,
+    csln: Option<&str>,
+) -> Result<Value, String> {
+    let parent = parent_snapshot(conn, layer.parent_id, layer.version).await?;
+    overrides_against(&parent, &layer.overrides, intent, csln)
+}
+
+fn overrides_against(parent: &Parent, current: &Value, intent: &Value, csln: Option<&str>) -> Result<Value, String> {
+    let csln_patch = match csln.filter(|c| !c.trim().is_empty()) {
+        Some(csln) => diff::make_patch(&yaml_value(&base_csln(parent, intent)?)?, &yaml_value(csln)?),
+        None => current.get("csln").cloned().unwrap_or_else(|| json!({})),
+    };
+    Ok(json!({
+        "intent": diff::make_patch(&parent.intent, intent),
+        "csln": csln_patch,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generated(intent: &Value) -> csln_core::Style {
+        serde_json::from_value::<StyleIntent>(intent.clone()).unwrap().to_style()
+    }
+
+    #[test]
+    fn overrides_for_a_past_version_resolve_back_to_it() {
+        let parent_intent = json!({"class": "author_date", "has_bibliography": true});
+        let parent = Parent {
+            csln: Some(serde_yaml::to_string(&generated(&parent_intent)).unwrap()),
+            intent: parent_intent,
+        };
+
+        // The version being restored changed the class and hand-edited the title
+        let intent = json!({"class": "numeric", "has_bibliography": true});
+        let mut style = generated(&intent);
+        style.info.title = Some("Restored".to_string());
+        let csln = serde_yaml::to_string(&style).unwrap();
+
+        // Overrides since the version, which a rollback must not keep
+        let current = json!({"intent": {"class": "note"}, "csln": {"info": {"title": "Later"}}});
+
+        let overrides = overrides_against(&parent, &current, &intent, Some(&csln)).unwrap();
+        let (resolved_intent, reconciled) = apply(&parent, &overrides).unwrap();
+        assert_eq!(resolved_intent["class"], "numeric");
+        assert_eq!(reconciled.source, SOURCE_CSLN);
+        assert_eq!(yaml_value(&reconciled.csln).unwrap(), yaml_value(&csln).unwrap());
+    }
+}
@@ -0,0 +1,93 @@
+use axum::http::Uri;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+/// The `limit` and `cursor` query parameters shared by list endpoints.
+#[derive(Deserialize)]
+pub struct PageParams {
+    limit: Option<i64>,
+    /// The `next_cursor` of the previous page.
+    cursor: Option<String>,
+}
+
+/// A position in a list ordered by `(key DESC, id DESC)`, written as
+/// `<key>.<id>`. Keys are timestamps in microseconds, counts or version
+/// numbers.
+#[derive(Debug, Clone, Copy)]
+pub struct Cursor {
+    pub key: i64,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn at_time(time: chrono::DateTime<chrono::Utc>, id: Uuid) -> Self {
+        Cursor { key: time.timestamp_micros(), id }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let (key, id) = text.split_once('.')?;
+        Some(Cursor { key: key.parse().ok()?, id: id.parse().ok()? })
+    }
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.key, self.id)
+    }
+}
+
+impl PageParams {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    /// Rows to fetch: one more than the limit, to tell whether a next page exists.
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit() + 1
+    }
+
+    /// The position to continue after, or `Err` for a malformed cursor.
+    pub fn after(&self) -> Result<Option<Cursor>, ()> {
+        match &self.cursor {
+            Some(cursor) => Cursor::parse(cursor).map(Some).ok_or(()),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The response envelope of every list endpoint.
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Pass as `cursor` to fetch the next page; absent on the last page.
+    pub next_cursor: Option<String>,
+    /// The request's URL with `cursor` set to `next_cursor`.
+    pub next: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Builds a page from up to `params.fetch_limit()` rows, given the
+    /// cursor of each row.
+    pub fn new(mut rows: Vec<T>, params: &PageParams, uri: &Uri, cursor: impl Fn(&T) -> Cursor) -> Self {
+        let limit = params.limit() as usize;
+        let next_cursor = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().map(|row| cursor(row).to_string())
+        } else {
+            None
+        };
+        let next = next_cursor.as_ref().map(|next| {
+            let mut query: Vec<String> = uri.query().unwrap_or_default()
+                .split('&')
+                .filter(|pair| !pair.is_empty() && !pair.starts_with("cursor="))
+                .map(str::to_string)
+                .collect();
+            query.push(format!("cursor={}", next));
+            format!("{}?{}", uri.path(), query.join("&"))
+        });
+        Page { items: rows, next_cursor, next }
+    }
+}
@@ -0,0 +1,312 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{StatusCode, Uri},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::pagination::{Cursor, Page, PageParams};
+use crate::{auth, AppState};
+
+/// A `MAJOR.MINOR.PATCH` version. Pre-release and build suffixes aren't
+/// supported: every release is a final one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Semver {
+    pub major: i32,
+    pub minor: i32,
+    pub patch: i32,
+}
+
+impl Semver {
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.trim().trim_start_matches('v').split('.');
+        let mut next = || parts.next()?.parse::<i32>().ok().filter(|n| *n >= 0);
+        let version = Semver { major: next()?, minor: next()?, patch: next()? };
+        parts.next().is_none().then_some(version)
+    }
+
+    fn bump(self, bump: Bump) -> Self {
+        match bump {
+            Bump::Major => Semver { major: self.major + 1, minor: 0, patch: 0 },
+            Bump::Minor => Semver { minor: self.minor + 1, patch: 0, ..self },
+            Bump::Patch => Semver { patch: self.patch + 1, ..self },
+        }
+    }
+}
+
+impl std::fmt::Display for Semver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Bump {
+    Major,
+    Minor,
+    Patch,
+}
+
+#[derive(Deserialize)]
+pub struct PublishRequest {
+    /// An explicit version, e.g. "1.2.0". Takes precedence over `bump`.
+    version: Option<String>,
+    /// Which part of the latest release to increment; defaults to minor.
+    bump: Option<Bump>,
+    #[serde(default)]
+    changelog: String,
+}
+
+#[derive(Serialize)]
+pub struct Release {
+    style_id: Uuid,
+    version: String,
+    changelog: String,
+    title: String,
+    intent: Value,
+    csln: Option<String>,
+    published_by: Option<Uuid>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize)]
+pub struct ReleaseSummary {
+    #[serde(skip)]
+    id: Uuid,
+    #[serde(skip)]
+    major: i32,
+    version: String,
+    changelog: String,
+    published_by: Option<Uuid>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+async fn latest_version(conn: &mut sqlx::PgConnection, style_id: Uuid) -> Option<Semver> {
+    sqlx::query!(
+        "SELECT major, minor, patch FROM releases WHERE style_id = $1 ORDER BY major DESC, minor DESC, patch DESC LIMIT 1",
+        style_id
+    )
+    .fetch_optional(conn)
+    .await
+    .expect("Failed to fetch releases")
+    .map(|r| Semver { major: r.major, minor: r.minor, patch: r.patch })
+}
+
+/// Picks the version to publish: the requested one, or the latest release
+/// bumped. It must be greater than the latest release.
+fn next_version(requested: Option<&str>, bump: Option<Bump>, latest: Option<Semver>) -> Result<Semver, (StatusCode, String)> {
+    let version = match (requested, latest) {
+        (Some(text), _) => Semver::parse(text)
+            .ok_or_else(|| (StatusCode::UNPROCESSABLE_ENTITY, format!("'{}' is not a MAJOR.MINOR.PATCH version", text)))?,
+        (None, Some(latest)) => latest.bump(bump.unwrap_or(Bump::Minor)),
+        (None, None) => Semver { major: 1, minor: 0, patch: 0 },
+    };
+    match latest {
+        Some(latest) if version <= latest => {
+            Err((StatusCode::CONFLICT, format!("Version {} must be greater than the latest release {}", version, latest)))
+        },
+        _ => Ok(version),
+    }
+}
+
+/// Publishes the style's current draft as a new immutable release. The
+/// version must be greater than every earlier release.
+///
+/// The style row is locked while the version is chosen, so concurrent
+/// publishes of the same style run one after the other.
+pub async fn publish(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<PublishRequest>,
+) -> impl IntoResponse {
+    let mut tx = state.db.begin().await.expect("Failed to start transaction");
+    let draft = sqlx::query!(
+        "SELECT title, intent, csln FROM styles WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL FOR UPDATE",
+        id,
+        user.id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("Failed to fetch style");
+
+    let draft = match draft {
+        Some(d) => d,
+        None => return (StatusCode::NOT_FOUND, "Style not found").into_response(),
+    };
+
+    let latest = latest_version(&mut tx, id).await;
+    let version = match next_version(payload.version.as_deref(), payload.bump, latest) {
+        Ok(version) => version,
+        Err(e) => return e.into_response(),
+    };
+
+    let release = sqlx::query_as!(
+        Release,
+        r#"
+        INSERT INTO releases (style_id, version, major, minor, patch, changelog, title, intent, csln, published_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING style_id, version, changelog, title, intent, csln, published_by, created_at
+        "#,
+        id,
+        version.to_string(),
+        version.major,
+        version.minor,
+        version.patch,
+        payload.changelog,
+        draft.title,
+        draft.intent,
+        draft.csln,
+        user.id
+    )
+    .fetch_one(&mut *tx)
+    .await;
+
+    match release {
+        Ok(release) => {
+            tx.commit().await.expect("Failed to commit release");
+            (StatusCode::CREATED, Json(release)).into_response()
+        },
+        // A concurrent publish took the same version
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            (StatusCode::CONFLICT, format!("Version {} already exists", version)).into_response()
+        },
+        Err(e) => {
+            println!("Failed to publish {} {}: {}", id, version, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to publish release").into_response()
+        },
+    }
+}
+
+/// Lists releases newest version first. Releases are never deleted, so a
+/// page continues after the version of the release its cursor names.
+pub async fn list_releases(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path(id): Path<Uuid>,
+    uri: Uri,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let after = match page.after() {
+        Ok(after) => after,
+        Err(()) => return (StatusCode::BAD_REQUEST, "Invalid cursor").into_response(),
+    };
+
+    let releases = sqlx::query_as!(
+        ReleaseSummary,
+        r#"
+        SELECT r.id, r.major, r.version, r.changelog, r.published_by, r.created_at
+        FROM releases r
+        JOIN styles s ON s.id = r.style_id
+        WHERE r.style_id = $1 AND (s.user_id = $2 OR s.is_public = true) AND s.deleted_at IS NULL
+          AND ($3::UUID IS NULL OR (r.major, r.minor, r.patch) < (SELECT major, minor, patch FROM releases WHERE id = $3))
+        ORDER BY r.major DESC, r.minor DESC, r.patch DESC
+        LIMIT $4
+        "#,
+        id,
+        user.id,
+        after.map(|c| c.id),
+        page.fetch_limit()
+    )
+    .fetch_all(&state.db)
+    .await
+    .expect("Failed to fetch releases");
+
+    Json(Page::new(releases, &page, &uri, |r| Cursor { key: r.major.into(), id: r.id })).into_response()
+}
+
+/// Serves a published release. Its CSLN is frozen at publish time, so
+/// documents citing a version keep rendering the same way.
+pub async fn get_release(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path((id, semver)): Path<(Uuid, String)>,
+) -> impl IntoResponse {
+    let version = match Semver::parse(&semver) {
+        Some(v) => v.to_string(),
+        None => return (StatusCode::UNPROCESSABLE_ENTITY, format!("'{}' is not a MAJOR.MINOR.PATCH version", semver)).into_response(),
+    };
+
+    let release = sqlx::query_as!(
+        Release,
+        r#"
+        SELECT r.style_id, r.version, r.changelog, r.title, r.intent, r.csln, r.published_by, r.created_at
+        FROM releases r
+        JOIN styles s ON s.id = r.style_id
+        WHERE r.style_id = $1 AND r.version = $2 AND (s.user_id = $3 OR s.is_public = true) AND s.deleted_at IS NULL
+        "#,
+        id,
+        version,
+        user.id
+    )
+    .fetch_optional(&state.db)
+    .await
+    .expect("Failed to fetch release");
+
+    match release {
+        Some(release) => Json(release).into_response(),
+        None => (StatusCode::NOT_FOUND, "Release not found").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(major: i32, minor: i32, patch: i32) -> Semver {
+        Semver { major, minor, patch }
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(Semver::parse("1.2.3"), Some(v(1, 2, 3)));
+        assert_eq!(Semver::parse(" v10.0.12 "), Some(v(10, 0, 12)));
+        assert_eq!(v(1, 2, 3).to_string(), "1.2.3");
+    }
+
+    #[test]
+    fn rejects_malformed_versions() {
+        for text in ["", "1", "1.2", "1.2.3.4", "1.-2.3", "1.2.x", "1.2.3-beta", "1..3"] {
+            assert_eq!(Semver::parse(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn orders_numerically() {
+        assert!(v(1, 10, 0) > v(1, 9, 9));
+        assert!(v(2, 0, 0) > v(1, 99, 99));
+        assert!(v(1, 0, 1) > v(1, 0, 0));
+    }
+
+    #[test]
+    fn bumps_reset_lower_parts() {
+        assert_eq!(v(1, 2, 3).bump(Bump::Major), v(2, 0, 0));
+        assert_eq!(v(1, 2, 3).bump(Bump::Minor), v(1, 3, 0));
+        assert_eq!(v(1, 2, 3).bump(Bump::Patch), v(1, 2, 4));
+    }
+
+    #[test]
+    fn first_release_is_one_point_oh() {
+        assert_eq!(next_version(None, Some(Bump::Patch), None), Ok(v(1, 0, 0)));
+        assert_eq!(next_version(Some("0.1.0"), None, None), Ok(v(0, 1, 0)));
+    }
+
+    #[test]
+    fn next_version_bumps_minor_by_default() {
+        assert_eq!(next_version(None, None, Some(v(1, 2, 3))), Ok(v(1, 3, 0)));
+        assert_eq!(next_version(None, Some(Bump::Major), Some(v(1, 2, 3))), Ok(v(2, 0, 0)));
+    }
+
+    #[test]
+    fn explicit_versions_must_move_forward() {
+        assert_eq!(next_version(Some("1.2.4"), Some(Bump::Major), Some(v(1, 2, 3))), Ok(v(1, 2, 4)));
+        assert!(matches!(next_version(Some("1.2.3"), None, Some(v(1, 2, 3))), Err((StatusCode::CONFLICT, _))));
+        assert!(matches!(next_version(Some("1.0.0"), None, Some(v(1, 2, 3))), Err((StatusCode::CONFLICT, _))));
+        assert!(matches!(next_version(Some("one"), None, None), Err((StatusCode::UNPROCESSABLE_ENTITY, _))));
+    }
+}
@@ -0,0 +1,208 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{StatusCode, Uri},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::pagination::{Cursor, Page, PageParams};
+use crate::{auth, AppState, StyleRow};
+
+/// How long a deleted style can be restored before it is purged.
+const RETENTION_DAYS: i32 = 30;
+
+/// Styles deleted at or before the cutoff have outlived the retention
+/// period: they can no longer be restored and the next purge removes them.
+fn retention_cutoff(now: DateTime<Utc>) -> DateTime<Utc> {
+    now - chrono::Duration::days(RETENTION_DAYS.into())
+}
+
+/// When a style deleted at `deleted_at` reaches the cutoff.
+fn purge_at(deleted_at: DateTime<Utc>) -> DateTime<Utc> {
+    deleted_at + chrono::Duration::days(RETENTION_DAYS.into())
+}
+
+#[derive(Serialize)]
+pub struct TrashedStyle {
+    id: Uuid,
+    title: String,
+    deleted_at: DateTime<Utc>,
+    /// When the purge job will remove the style for good.
+    purge_at: DateTime<Utc>,
+}
+
+/// Moves a style to its owner's trash. It disappears from the hub, from
+/// bookmarks and from forking, but forks already made are independent
+/// copies and keep working.
+///
+/// Published styles can't be deleted: documents cite their releases.
+pub async fn delete_style(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let result = sqlx::query!(
+        r#"
+        UPDATE styles SET deleted_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL
+          AND NOT EXISTS (SELECT 1 FROM releases WHERE style_id = styles.id)
+        "#,
+        id,
+        user.id
+    )
+    .execute(&state.db)
+    .await
+    .expect("Failed to delete style");
+
+    if result.rows_affected() == 0 {
+        let published = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM styles s JOIN releases r ON r.style_id = s.id
+                WHERE s.id = $1 AND s.user_id = $2 AND s.deleted_at IS NULL
+            ) AS "published!"
+            "#,
+            id,
+            user.id
+        )
+        .fetch_one(&state.db)
+        .await
+        .expect("Failed to check releases");
+
+        if published {
+            return (StatusCode::CONFLICT, "Published styles can't be deleted").into_response();
+        }
+        return (StatusCode::NOT_FOUND, "Style not found").into_response();
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
+
+pub async fn list_trash(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    uri: Uri,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let after = match page.after() {
+        Ok(after) => after,
+        Err(()) => return (StatusCode::BAD_REQUEST, "Invalid cursor").into_response(),
+    };
+
+    let styles = sqlx::query!(
+        r#"
+        SELECT id, title, deleted_at AS "deleted_at!"
+        FROM styles
+        WHERE user_id = $1 AND deleted_at IS NOT NULL
+          AND ($2::BIGINT IS NULL OR ((EXTRACT(EPOCH FROM deleted_at) * 1000000)::BIGINT, id) < ($2, $3::UUID))
+        ORDER BY deleted_at DESC, id DESC
+        LIMIT $4
+        "#,
+        user.id,
+        after.map(|c| c.key),
+        after.map(|c| c.id),
+        page.fetch_limit()
+    )
+    .fetch_all(&state.db)
+    .await
+    .expect("Failed to fetch trash")
+    .into_iter()
+    .map(|r| TrashedStyle { id: r.id, title: r.title, deleted_at: r.deleted_at, purge_at: purge_at(r.deleted_at) })
+    .collect();
+
+    Json(Page::new(styles, &page, &uri, |s| Cursor::at_time(s.deleted_at, s.id))).into_response()
+}
+
+pub async fn restore_style(
+    State(state): State<Arc<AppState>>,
+    user: auth::AuthenticatedUser,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let style = sqlx::query_as!(
+        StyleRow,
+        r#"
+        UPDATE styles
+        SET deleted_at = NULL
+        WHERE id = $1 AND user_id = $2 AND deleted_at > $3
+        RETURNING id, user_id, title, intent, csln, csln_source, is_public, revision, created_at, updated_at
+        "#,
+        id,
+        user.id,
+        retention_cutoff(Utc::now())
+    )
+    .fetch_optional(&state.db)
+    .await
+    .expect("Failed to restore style");
+
+    match style {
+        Some(s) => Json(s).into_response(),
+        None => (StatusCode::NOT_FOUND, "Style not found in trash").into_response(),
+    }
+}
+
+/// Deletes styles that have been in the trash longer than the retention
+/// period. Their history and bookmarks go with them. Styles with releases
+/// are never purged, so published versions keep resolving.
+pub async fn purge_expired(db: &sqlx::PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM styles
+        WHERE deleted_at <= $1
+          AND NOT EXISTS (SELECT 1 FROM releases WHERE style_id = styles.id)
+        "#,
+        retention_cutoff(Utc::now())
+    )
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Runs `purge_expired` hourly for the life of the server.
+pub fn spawn_purge_job(db: sqlx::PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            match purge_expired(&db).await {
+                Ok(0) => {},
+                Ok(n) => println!("Purged {} styles from the trash", n),
+                Err(e) => println!("Failed to purge trash: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn cutoff_is_the_retention_period_ago() {
+        assert_eq!(retention_cutoff(at(31, 12)), at(1, 12));
+    }
+
+    #[test]
+    fn a_style_expires_at_its_purge_time() {
+        let deleted_at = at(1, 12);
+        // Restore needs deleted_at > cutoff, purge takes deleted_at <= cutoff
+        let just_before = purge_at(deleted_at) - chrono::Duration::seconds(1);
+        assert!(deleted_at > retention_cutoff(just_before));
+        assert!(deleted_at <= retention_cutoff(purge_at(deleted_at)));
+    }
+
+    #[test]
+    fn purge_time_is_reported_from_the_same_period() {
+        let deleted_at = at(1, 12);
+        assert_eq!(retention_cutoff(purge_at(deleted_at)), deleted_at);
+        assert_eq!(purge_at(deleted_at), at(31, 12));
+    }
+}